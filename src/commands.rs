@@ -1,10 +1,23 @@
 automod::dir!("src/commands");
 
-use anyhow::Result;
+use std::{collections::VecDeque, env, path::PathBuf};
+
+use anyhow::{bail, Result};
 use clap::Subcommand;
+use futures::future::try_join3;
+use indexmap::IndexMap;
+use serde_json::json;
 
-use crate::{compose, config::Config, podman::Podman};
+use crate::{
+    compose::{self, types::Compose},
+    config::Config,
+    podman::{
+        types::{Container, Network, Pod, PodLabels, Volume},
+        Podman,
+    },
+};
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub(crate) enum Command {
     #[command(flatten)]
@@ -35,40 +48,547 @@ pub(crate) enum ExtCommand {
     Top(top::Args),
     Port(port::Args),
     Ls(ls::Args),
+    Prune(prune::Args),
+}
+
+/// Rebuilds the project from the labels of its pod, or of its containers without one, when its
+/// Compose file cannot be found
+async fn rehydrate(podman: &Podman, config: &mut Config, command: &ExtCommand) -> Result<Compose> {
+    let name = config.project_name.clone().unwrap();
+    let output = podman
+        .force_run([
+            "pod",
+            "ps",
+            "--format",
+            "json",
+            "--filter",
+            &format!("label=io.podman.compose.project={name}"),
+        ])
+        .await?;
+    let mut pods = serde_json::from_str::<VecDeque<Pod>>(&output)?;
+
+    // Prefer the pod holding the project labels over those created by `podman kube play`
+    pods.make_contiguous().sort_by_key(|pod| {
+        pod.labels
+            .as_ref()
+            .and_then(|labels| labels.config_files.as_ref())
+            .is_none()
+    });
+
+    let labels = match pods.pop_front() {
+        Some(pod) => pod.labels,
+        // Projects without a pod carry the same labels on each of their containers
+        None => {
+            let containers = serde_json::from_str::<Vec<Container>>(
+                &podman
+                    .force_run([
+                        "ps",
+                        "--all",
+                        "--format",
+                        "json",
+                        "--filter",
+                        &format!("label=io.podman.compose.project={name}"),
+                    ])
+                    .await?,
+            )?;
+
+            if containers.is_empty() {
+                bail!("Compose file not found in the working directory or its parent directories, and project \"{name}\" does not exist");
+            }
+
+            containers
+                .into_iter()
+                .filter_map(|container| container.labels)
+                .find(|labels| labels.config_files.is_some())
+                .map(PodLabels::from)
+        }
+    };
+    let files = labels
+        .as_ref()
+        .and_then(|labels| labels.config_files.as_ref())
+        .map(|files| files.split(',').map(PathBuf::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    if !files.is_empty() && files.iter().all(|file| file.is_file()) {
+        if let Some(env_file) = labels
+            .as_ref()
+            .and_then(|labels| labels.environment_file.as_ref())
+            .map(PathBuf::from)
+            .filter(|env_file| env_file.is_file())
+        {
+            dotenvy::from_path(&env_file)?;
+            config.env_file = env_file;
+        }
+
+        if let Some(project_directory) = labels
+            .as_ref()
+            .and_then(|labels| labels.working_dir.as_ref())
+            .map(PathBuf::from)
+            .filter(|project_directory| project_directory.is_dir())
+        {
+            env::set_current_dir(&project_directory)?;
+            config.project_directory = project_directory;
+        }
+
+        config.files = files;
+
+        return compose::parse(&config.project_name, &config.files, &config.profiles, false);
+    }
+
+    // Without the Compose file, only teardown and inspection are possible
+    if !matches!(
+        command,
+        ExtCommand::Down(_)
+            | ExtCommand::Rm(_)
+            | ExtCommand::Stop(_)
+            | ExtCommand::Kill(_)
+            | ExtCommand::Pause(_)
+            | ExtCommand::Unpause(_)
+            | ExtCommand::Events(_)
+            | ExtCommand::Logs(_)
+            | ExtCommand::Ps(_)
+            | ExtCommand::Top(_)
+            | ExtCommand::Port(_)
+            | ExtCommand::Prune(_)
+    ) {
+        bail!("Compose file of project \"{name}\" not found, only teardown and inspection commands are available");
+    }
+
+    let (containers, networks, volumes) = try_join3(
+        podman.force_run([
+            "ps",
+            "--all",
+            "--format",
+            "json",
+            "--filter",
+            &format!("label=io.podman.compose.project={name}"),
+        ]),
+        podman.force_run([
+            "network",
+            "ls",
+            "--format",
+            "json",
+            "--filter",
+            &format!("label=io.podman.compose.project={name}"),
+        ]),
+        podman.force_run([
+            "volume",
+            "ls",
+            "--format",
+            "json",
+            "--filter",
+            &format!("label=io.podman.compose.project={name}"),
+        ]),
+    )
+    .await?;
+
+    let services = serde_json::from_str::<Vec<Container>>(&containers)?
+        .into_iter()
+        .filter_map(|container| container.labels.and_then(|labels| labels.service))
+        .map(|service| (service, json!({})))
+        .collect::<IndexMap<_, _>>();
+    let networks = serde_json::from_str::<Vec<Network>>(&networks)?
+        .into_iter()
+        .map(|network| (network.name.clone(), json!({ "name": network.name })))
+        .collect::<IndexMap<_, _>>();
+    let volumes = serde_json::from_str::<Vec<Volume>>(&volumes)?
+        .into_iter()
+        .map(|volume| (volume.name.clone(), json!({ "name": volume.name })))
+        .collect::<IndexMap<_, _>>();
+
+    Ok(serde_json::from_value(json!({
+        "name": name,
+        "services": services,
+        "networks": networks,
+        "volumes": volumes,
+    }))?)
+}
+
+/// Runs a command against the project, with the given Podman executor
+async fn execute(command: ExtCommand, podman: &Podman, mut config: Config) -> Result<()> {
+    let file = if let ExtCommand::Ls(_) = command {
+        Compose::new()
+    } else if config.files.is_empty() {
+        rehydrate(podman, &mut config, &command).await?
+    } else {
+        compose::parse(&config.project_name, &config.files, &config.profiles, false)?
+    };
+
+    match command {
+        ExtCommand::Up(args) => up::run(args, podman, &file, &config).await,
+        ExtCommand::Down(args) => down::run(args, podman, &file, &config).await,
+        ExtCommand::Create(args) => create::run(args, podman, &file, &config).await,
+        ExtCommand::Rm(args) => rm::run(args, podman, &file, &config).await,
+        ExtCommand::Start(args) => start::run(args, podman, &file, &config).await,
+        ExtCommand::Stop(args) => stop::run(args, podman, &file, &config).await,
+        ExtCommand::Restart(args) => restart::run(args, podman, &file, &config).await,
+        ExtCommand::Kill(args) => kill::run(args, podman, &file, &config).await,
+        ExtCommand::Pause(args) => pause::run(args, podman, &file, &config).await,
+        ExtCommand::Unpause(args) => unpause::run(args, podman, &file, &config).await,
+        ExtCommand::Run(args) => run::run(args, podman, &file, &config).await,
+        ExtCommand::Exec(args) => exec::run(args, podman, &file).await,
+        ExtCommand::Cp(args) => cp::run(args, podman, &file).await,
+        ExtCommand::Events(args) => events::run(args, podman, &file).await,
+        ExtCommand::Logs(args) => logs::run(args, podman, &file).await,
+        ExtCommand::Ps(args) => ps::run(args, podman, &file).await,
+        ExtCommand::Top(args) => top::run(args, podman, &file).await,
+        ExtCommand::Port(args) => port::run(args, podman, &file).await,
+        ExtCommand::Ls(args) => ls::run(args, podman).await,
+        ExtCommand::Prune(args) => prune::run(args, podman, &file, &config).await,
+    }
 }
 
 pub(crate) async fn run(command: Command, config: Config) -> Result<()> {
     match command {
         Command::ExtCommand(command) => {
             let podman = Podman::new(&config).await?;
-            let file =
-                compose::parse(&config.project_name, &config.files, &config.profiles, false)?;
-
-            match command {
-                ExtCommand::Up(args) => up::run(args, &podman, &file, &config).await,
-                ExtCommand::Down(args) => down::run(args, &podman, &file, &config).await,
-                ExtCommand::Create(args) => create::run(args, &podman, &file, &config).await,
-                ExtCommand::Rm(args) => rm::run(args, &podman, &file, &config).await,
-                ExtCommand::Start(args) => start::run(args, &podman, &file, &config).await,
-                ExtCommand::Stop(args) => stop::run(args, &podman, &file, &config).await,
-                ExtCommand::Restart(args) => restart::run(args, &podman, &file, &config).await,
-                ExtCommand::Kill(args) => kill::run(args, &podman, &file, &config).await,
-                ExtCommand::Pause(args) => pause::run(args, &podman, &file, &config).await,
-                ExtCommand::Unpause(args) => unpause::run(args, &podman, &file, &config).await,
-                ExtCommand::Run(args) => run::run(args, &podman, &file, &config).await,
-                ExtCommand::Exec(args) => exec::run(args, &podman, &file).await,
-                ExtCommand::Cp(args) => cp::run(args, &podman, &file).await,
-                ExtCommand::Events(args) => events::run(args, &podman, &file).await,
-                ExtCommand::Logs(args) => logs::run(args, &podman, &file).await,
-                ExtCommand::Ps(args) => ps::run(args, &podman, &file).await,
-                ExtCommand::Top(args) => top::run(args, &podman, &file).await,
-                ExtCommand::Port(args) => port::run(args, &podman, &file).await,
-                ExtCommand::Ls(args) => ls::run(args, &podman).await,
-            }?;
+
+            execute(command, &podman, config).await?;
+            podman.print_plan()?;
+        }
+        Command::Convert(args) => {
+            if config.files.is_empty() {
+                bail!("Compose file not found in the working directory or its parent directories");
+            }
+
+            convert::run(args, &config)?;
         }
-        Command::Convert(args) => convert::run(args, &config)?,
         Command::Version(args) => version::run(args),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, sync::Mutex, time::Duration};
+
+    use clap::{crate_version, Parser};
+    use itertools::Itertools;
+    use pretty_assertions::assert_eq;
+    use regex::{NoExpand, Regex};
+    use test_generator::test_resources;
+
+    use super::*;
+    use once_cell::sync::Lazy;
+
+    use crate::{
+        commands::convert::{kube::Kind, Format},
+        podman::fake::Fake,
+        progress::ProgressMode,
+    };
+
+    /// Commands run over every fixture, with `{service}` standing for its first service
+    const COMMANDS: [&str; 18] = [
+        "up --detach",
+        "create",
+        "start",
+        "stop",
+        "restart",
+        "kill",
+        "pause",
+        "unpause",
+        "ps",
+        "top",
+        "logs",
+        "events",
+        "events {service}",
+        "exec -T {service} true",
+        "run -T --rm {service} true",
+        "rm --force --stop",
+        "down --volumes",
+        "prune --force",
+    ];
+
+    /// Serialises the golden tests, which change the working directory like `main` does
+    static WORKING_DIR: Mutex<()> = Mutex::new(());
+
+    /// Directory the fixtures are copied to in turn, which must not change as the working
+    /// directory is cached when paths get absolutized
+    static PROJECT_DIRECTORY: Lazy<PathBuf> =
+        Lazy::new(|| env::temp_dir().join(format!("haddock-golden-{}", fastrand::u64(..))));
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: ExtCommand,
+    }
+
+    fn copy(source: &Path, destination: &Path) {
+        fs::create_dir_all(destination).unwrap();
+
+        for entry in fs::read_dir(source).unwrap() {
+            let entry = entry.unwrap();
+            let path = destination.join(entry.file_name());
+
+            if entry.file_type().unwrap().is_dir() {
+                copy(&entry.path(), &path);
+            } else {
+                fs::copy(entry.path(), path).unwrap();
+            }
+        }
+    }
+
+    /// Scripts podman as if every service had one running container and nothing else existed
+    fn fake(file: &Compose) -> Fake {
+        let name = file.name.as_ref().unwrap();
+        let containers = file
+            .services
+            .iter()
+            .map(|(service_name, service)| {
+                json!({
+                    "Id": format!("{service_name}-id"),
+                    "Image": service.image.clone().unwrap_or_default(),
+                    "Names": [service
+                        .container_name
+                        .clone()
+                        .unwrap_or_else(|| format!("{name}_{service_name}_1"))],
+                    "Labels": {
+                        "io.podman.compose.project": name,
+                        "io.podman.compose.service": service_name,
+                        "io.podman.compose.container-number": "1",
+                        "io.podman.compose.oneoff": "false",
+                    },
+                    "State": "running",
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Nothing exists when probed, while the project's containers are listed as running
+        Fake::default()
+            .script_exact(&["ps", "--all", "--format", "json"], Ok("[]"))
+            .script(&["ps"], Ok(&serde_json::to_string(&containers).unwrap()))
+            .script(&["pod", "ps"], Ok("[]"))
+            .script(&["network", "ls"], Ok("[]"))
+            .script(&["volume", "ls"], Ok("[]"))
+            .script(&["secret", "ls"], Ok("[]"))
+            .script(&["image", "ls"], Ok("[]"))
+            .delay(Duration::from_secs(1))
+    }
+
+    /// Runs every command over the fixture and compares the podman invocations against
+    /// `tests/golden`, which `UPDATE_GOLDEN=1` rewrites
+    #[test_resources("tests/fixtures/*/*compose.y*ml")]
+    fn golden(resource: &str) {
+        let _lock = WORKING_DIR.lock().unwrap_or_else(|err| err.into_inner());
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let resource = manifest_dir.join(resource);
+        let fixture = resource.parent().unwrap();
+        let fixture_name = fixture.file_name().unwrap().to_string_lossy().to_string();
+
+        // Run in a copy of the fixture, as bind mount sources get created
+        let project_directory = PROJECT_DIRECTORY.clone();
+        copy(fixture, &project_directory);
+        env::set_current_dir(&project_directory).unwrap();
+
+        let config = || Config {
+            project_name: Some(fixture_name.clone()),
+            files: vec![project_directory.join(resource.file_name().unwrap())],
+            project_directory: project_directory.clone(),
+            progress: ProgressMode::Quiet,
+            ..Config::default()
+        };
+        let file = compose::parse(&config().project_name, &config().files, &[], false).unwrap();
+
+        // Fixtures don't ship the files of their secrets
+        for secret in file.secrets.values() {
+            if let Some(path) = &secret.file {
+                if !path.exists() {
+                    fs::write(path, "secret").unwrap();
+                }
+            }
+        }
+
+        // Nor the variables of their environment secrets
+        let variables = file
+            .secrets
+            .values()
+            .filter_map(|secret| secret.environment.as_deref())
+            .map(|environment| (environment, Some("secret")))
+            .collect::<Vec<_>>();
+
+        let service = file.services.keys().next().unwrap();
+        // With the clock paused, time only advances once every runnable task is waiting, so each
+        // invocation stays in flight until all the ones it runs concurrently with have started
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        let output = temp_env::with_vars(variables, || {
+            let mut output = String::new();
+
+            for command in COMMANDS {
+                let command = command.replace("{service}", service);
+                let fake = fake(&file);
+                let invocations = fake.invocations();
+                let podman = Podman::with_executor(Box::new(fake), &config());
+                let result = runtime.block_on(execute(
+                    Cli::try_parse_from(["haddock"].into_iter().chain(command.split(' ')))
+                        .unwrap()
+                        .command,
+                    &podman,
+                    config(),
+                ));
+
+                output.push_str(&format!("$ haddock {command}\n"));
+
+                // Invocations in flight at once run concurrently, in no particular order
+                for (_, batch) in &invocations.borrow().iter().group_by(|(batch, _)| batch) {
+                    for (_, invocation) in batch.sorted() {
+                        output.push_str(&format!("{invocation}\n"));
+                    }
+                }
+
+                if let Err(err) = result {
+                    output.push_str(&format!("! {err:#}\n"));
+                }
+
+                output.push('\n');
+            }
+
+            // Conversions don't run podman, so their output is compared instead
+            for (command, format, kind) in [
+                ("convert --format quadlet", Format::Quadlet, Kind::Pod),
+                ("convert --format kube", Format::Kube, Kind::Pod),
+                (
+                    "convert --format kube --kind deployment",
+                    Format::Kube,
+                    Kind::Deployment,
+                ),
+            ] {
+                output.push_str(&format!("$ haddock {command}\n"));
+
+                match convert::render(&file, &format, kind) {
+                    Ok(contents) => output.push_str(&contents),
+                    Err(err) => output.push_str(&format!("! {err:#}\n")),
+                }
+
+                output.push('\n');
+            }
+
+            output
+        });
+
+        env::set_current_dir(manifest_dir).unwrap();
+        fs::remove_dir_all(&project_directory).ok();
+
+        // Mask what depends on the machine, the release or chance
+        let output = [
+            (regex::escape(&project_directory.to_string_lossy()), "$DIR"),
+            (
+                format!(r"version=\b{}\b", regex::escape(crate_version!())),
+                "version=$VERSION",
+            ),
+            (
+                format!(r"version: \b{}\b", regex::escape(crate_version!())),
+                "version: $VERSION",
+            ),
+            (String::from(r"_run_[0-9a-f]{12}"), "_run_$ID"),
+            (
+                String::from(r"config-hash=[0-9a-f]{64}"),
+                "config-hash=$HASH",
+            ),
+            (
+                String::from(r"config-fields=[a-z0-9_:,]*"),
+                "config-fields=$FIELDS",
+            ),
+        ]
+        .into_iter()
+        .fold(output, |output, (pattern, replacement)| {
+            Regex::new(&pattern)
+                .unwrap()
+                .replace_all(&output, NoExpand(replacement))
+                .to_string()
+        });
+        let golden = manifest_dir
+            .join("tests/golden")
+            .join(format!("{fixture_name}.txt"));
+
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(golden.parent().unwrap()).unwrap();
+            fs::write(&golden, output).unwrap();
+        } else {
+            let expected = fs::read_to_string(&golden).unwrap_or_else(|_| {
+                panic!(
+                    "{} not found, run with UPDATE_GOLDEN=1 to create it",
+                    golden.display()
+                )
+            });
+
+            assert_eq!(output, expected);
+        }
+    }
+
+    /// Scripts podman with no pod and a container of project "solo" pointing at `directory`
+    fn pod_less(directory: &Path) -> Fake {
+        let container = json!([{
+            "Id": "web-id",
+            "Names": ["solo_web_1"],
+            "Labels": {
+                "io.podman.compose.project": "solo",
+                "io.podman.compose.project.working-dir": directory,
+                "io.podman.compose.project.config-files": directory.join("compose.yaml"),
+                "io.podman.compose.service": "web",
+            },
+            "State": "exited",
+        }]);
+
+        Fake::default()
+            .script(&["pod", "ps"], Ok("[]"))
+            .script(&["ps"], Ok(&container.to_string()))
+    }
+
+    #[test]
+    fn rehydrate_pod_less() {
+        let _lock = WORKING_DIR.lock().unwrap_or_else(|err| err.into_inner());
+        let working_dir = env::current_dir().unwrap();
+        let directory = env::temp_dir().join(format!("haddock-pod-less-{}", fastrand::u64(..)));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("compose.yaml"),
+            "services:\n  web:\n    image: nginx\n",
+        )
+        .unwrap();
+
+        let mut config = Config {
+            project_name: Some(String::from("solo")),
+            ..Config::default()
+        };
+        let podman = Podman::with_executor(Box::new(pod_less(&directory)), &config);
+        let file = tokio::runtime::Runtime::new().unwrap().block_on(rehydrate(
+            &podman,
+            &mut config,
+            &Cli::try_parse_from(["haddock", "up"]).unwrap().command,
+        ));
+
+        env::set_current_dir(working_dir).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            file.unwrap().services.keys().collect::<Vec<_>>(),
+            vec!["web"]
+        );
+        assert_eq!(config.files, vec![directory.join("compose.yaml")]);
+        assert_eq!(config.project_directory, directory);
+    }
+
+    #[test]
+    fn rehydrate_missing() {
+        let mut config = Config {
+            project_name: Some(String::from("solo")),
+            ..Config::default()
+        };
+        let fake = Fake::default()
+            .script(&["pod", "ps"], Ok("[]"))
+            .script(&["ps"], Ok("[]"));
+        let podman = Podman::with_executor(Box::new(fake), &config);
+        let result = tokio::runtime::Runtime::new().unwrap().block_on(rehydrate(
+            &podman,
+            &mut config,
+            &Cli::try_parse_from(["haddock", "down"]).unwrap().command,
+        ));
+
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
+    }
+}
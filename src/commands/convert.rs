@@ -6,9 +6,16 @@ use std::{
 use anyhow::{anyhow, Context, Error, Result};
 use clap::ValueEnum;
 use indexmap::IndexSet;
+use itertools::Itertools;
 use path_absolutize::Absolutize;
 
-use crate::{compose, config::Config};
+use crate::{
+    compose::{self, types::Compose},
+    config::Config,
+};
+
+pub(crate) mod kube;
+mod quadlet;
 
 /// Converts the Compose file to platform's canonical format
 #[derive(clap::Args, Debug)]
@@ -18,6 +25,10 @@ pub(crate) struct Args {
     #[arg(long, value_enum, default_value_t = Format::Yaml)]
     format: Format,
 
+    /// Kubernetes object to generate the containers in, for the kube format
+    #[arg(long, value_enum, default_value_t = kube::Kind::Pod)]
+    kind: kube::Kind,
+
     /// Only validate the configuration, don't print anything
     #[arg(short, long)]
     quiet: bool,
@@ -42,15 +53,30 @@ pub(crate) struct Args {
     #[arg(long)]
     images: bool,
 
-    /// Save to file (default to stdout)
+    /// Save to file, or to a directory for the quadlet format (default to stdout)
     #[arg(short, long)]
     output: Option<PathBuf>,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
-enum Format {
+pub(crate) enum Format {
     Yaml,
     Json,
+    Quadlet,
+    Kube,
+}
+
+/// Renders the Compose file in `format`, with the units of the quadlet format one after the other
+pub(crate) fn render(file: &Compose, format: &Format, kind: kube::Kind) -> Result<String> {
+    Ok(match format {
+        Format::Yaml => serde_yaml::to_string(file)?,
+        Format::Json => serde_json::to_string_pretty(file)? + "\n",
+        Format::Kube => kube::convert(file, kind)?,
+        Format::Quadlet => quadlet::convert(file)
+            .into_iter()
+            .map(|(name, unit)| format!("# {name}\n{unit}"))
+            .join("\n"),
+    })
 }
 
 pub(crate) fn run(args: Args, config: &Config) -> Result<()> {
@@ -88,18 +114,18 @@ pub(crate) fn run(args: Args, config: &Config) -> Result<()> {
                 println!("{image}");
             }
         } else {
-            let mut contents;
+            if let (Format::Quadlet, Some(path)) = (&args.format, &args.output) {
+                fs::create_dir_all(path)?;
 
-            match args.format {
-                Format::Yaml => {
-                    contents = serde_yaml::to_string(&file)?;
-                }
-                Format::Json => {
-                    contents = serde_json::to_string_pretty(&file)?;
-                    contents.push('\n');
+                for (name, unit) in quadlet::convert(&file) {
+                    fs::write(path.join(name), unit)?;
                 }
+
+                return Ok(());
             }
 
+            let contents = render(&file, &args.format, args.kind)?;
+
             if let Some(path) = args.output {
                 fs::write(&path, contents).with_context(|| match path.absolutize() {
                     Ok(path) => anyhow!(
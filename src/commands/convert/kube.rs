@@ -0,0 +1,907 @@
+use std::{env, fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use clap::{crate_version, ValueEnum};
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use serde::Serialize;
+use serde_with::skip_serializing_none;
+
+use crate::compose::{
+    types::{Compose, FileReferenceType, PullPolicy, RestartPolicy, Service, ServiceVolumeType},
+    STYLED_WARNING,
+};
+
+/// Service elements with an equivalent in the Kubernetes objects
+const SUPPORTED: [&str; 31] = [
+    "cap_add",
+    "cap_drop",
+    "command",
+    "cpus",
+    "depends_on",
+    "deploy",
+    "entrypoint",
+    "env_file",
+    "environment",
+    "extra_hosts",
+    "healthcheck",
+    "hostname",
+    "image",
+    "labels",
+    "mem_limit",
+    "mem_reservation",
+    "networks",
+    "ports",
+    "privileged",
+    "profiles",
+    "pull_policy",
+    "read_only",
+    "restart",
+    "scale",
+    "secrets",
+    "stdin_open",
+    "tmpfs",
+    "tty",
+    "user",
+    "volumes",
+    "working_dir",
+];
+
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub(crate) enum Kind {
+    /// A single pod holding the containers of all services
+    #[default]
+    Pod,
+    /// A deployment per service
+    Deployment,
+}
+
+#[skip_serializing_none]
+#[serde_with::apply(
+    IndexMap => #[serde(skip_serializing_if = "IndexMap::is_empty")]
+)]
+#[derive(Serialize, Default, Debug)]
+struct ObjectMeta {
+    name: Option<String>,
+    labels: IndexMap<String, String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Object {
+    api_version: &'static str,
+    kind: &'static str,
+    metadata: ObjectMeta,
+    #[serde(flatten)]
+    body: Body,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum Body {
+    Spec { spec: Spec },
+    Data { data: IndexMap<String, String> },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum Spec {
+    Pod(PodSpec),
+    Deployment(DeploymentSpec),
+    PersistentVolumeClaim(PersistentVolumeClaimSpec),
+}
+
+#[skip_serializing_none]
+#[serde_with::apply(
+    Vec => #[serde(skip_serializing_if = "Vec::is_empty")]
+)]
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PodSpec {
+    hostname: Option<String>,
+    host_aliases: Vec<HostAlias>,
+    restart_policy: Option<&'static str>,
+    containers: Vec<Container>,
+    volumes: Vec<Volume>,
+}
+
+#[derive(Serialize, Debug)]
+struct HostAlias {
+    ip: String,
+    hostnames: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DeploymentSpec {
+    replicas: u32,
+    selector: LabelSelector,
+    template: PodTemplateSpec,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LabelSelector {
+    match_labels: IndexMap<String, String>,
+}
+
+#[derive(Serialize, Debug)]
+struct PodTemplateSpec {
+    metadata: ObjectMeta,
+    spec: PodSpec,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PersistentVolumeClaimSpec {
+    access_modes: Vec<&'static str>,
+    resources: ResourceRequirements,
+}
+
+#[skip_serializing_none]
+#[serde_with::apply(
+    Vec => #[serde(skip_serializing_if = "Vec::is_empty")]
+)]
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Container {
+    name: String,
+    image: Option<String>,
+    image_pull_policy: Option<&'static str>,
+    command: Vec<String>,
+    args: Vec<String>,
+    working_dir: Option<String>,
+    env: Vec<EnvVar>,
+    env_from: Vec<EnvFromSource>,
+    ports: Vec<ContainerPort>,
+    volume_mounts: Vec<VolumeMount>,
+    resources: Option<ResourceRequirements>,
+    liveness_probe: Option<Probe>,
+    security_context: Option<SecurityContext>,
+    stdin: Option<bool>,
+    tty: Option<bool>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EnvVar {
+    name: String,
+    value: Option<String>,
+    value_from: Option<EnvVarSource>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EnvVarSource {
+    secret_key_ref: KeySelector,
+}
+
+#[derive(Serialize, Debug)]
+struct KeySelector {
+    name: String,
+    key: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EnvFromSource {
+    config_map_ref: LocalObjectReference,
+}
+
+#[derive(Serialize, Debug)]
+struct LocalObjectReference {
+    name: String,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ContainerPort {
+    container_port: u16,
+    host_port: Option<u16>,
+    #[serde(rename = "hostIP")]
+    host_ip: Option<String>,
+    protocol: String,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VolumeMount {
+    name: String,
+    mount_path: String,
+    sub_path: Option<String>,
+    read_only: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+struct Volume {
+    name: String,
+    #[serde(flatten)]
+    source: VolumeSource,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+enum VolumeSource {
+    #[serde(rename_all = "camelCase")]
+    PersistentVolumeClaim {
+        claim_name: String,
+    },
+    HostPath {
+        path: String,
+        r#type: Option<&'static str>,
+    },
+    EmptyDir {
+        medium: Option<&'static str>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Secret {
+        secret_name: String,
+        default_mode: Option<u32>,
+    },
+}
+
+#[serde_with::apply(
+    IndexMap => #[serde(skip_serializing_if = "IndexMap::is_empty")]
+)]
+#[derive(Serialize, Default, Debug)]
+struct ResourceRequirements {
+    limits: IndexMap<&'static str, String>,
+    requests: IndexMap<&'static str, String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Probe {
+    exec: ExecAction,
+    period_seconds: Option<u64>,
+    timeout_seconds: Option<u64>,
+    initial_delay_seconds: Option<u64>,
+    failure_threshold: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+struct ExecAction {
+    command: Vec<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SecurityContext {
+    privileged: Option<bool>,
+    read_only_root_filesystem: Option<bool>,
+    run_as_user: Option<i64>,
+    run_as_group: Option<i64>,
+    capabilities: Option<Capabilities>,
+}
+
+#[serde_with::apply(
+    Vec => #[serde(skip_serializing_if = "Vec::is_empty")]
+)]
+#[derive(Serialize, Debug)]
+struct Capabilities {
+    add: Vec<String>,
+    drop: Vec<String>,
+}
+
+/// Turns a Compose name into a valid Kubernetes object name
+pub(crate) fn object_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .join("-")
+}
+
+fn labels(name: &str) -> IndexMap<String, String> {
+    [("version", crate_version!()), ("project", name)]
+        .into_iter()
+        .map(|(key, value)| (format!("io.podman.compose.{key}"), value.to_string()))
+        .collect()
+}
+
+fn restart_policy(restart: &RestartPolicy) -> &'static str {
+    match restart {
+        RestartPolicy::No => "Never",
+        RestartPolicy::OnFailure => "OnFailure",
+        RestartPolicy::Always | RestartPolicy::UnlessStopped => "Always",
+    }
+}
+
+fn replicas(service: &Service) -> u32 {
+    service
+        .deploy
+        .as_ref()
+        .and_then(|deploy| deploy.replicas)
+        .or(service.scale)
+        .unwrap_or(1)
+}
+
+struct Converter<'a> {
+    file: &'a Compose,
+    name: &'a str,
+    objects: Vec<Object>,
+}
+
+impl<'a> Converter<'a> {
+    fn new(file: &'a Compose) -> Self {
+        Self {
+            file,
+            name: file.name.as_ref().unwrap(),
+            objects: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, kind: &'static str, name: String, body: Body) {
+        self.objects.push(Object {
+            api_version: if kind == "Deployment" {
+                "apps/v1"
+            } else {
+                "v1"
+            },
+            kind,
+            metadata: ObjectMeta {
+                name: Some(name),
+                labels: labels(self.name),
+            },
+            body,
+        });
+    }
+
+    fn secrets(&mut self) -> Result<()> {
+        for (key, secret) in &self.file.secrets {
+            let secret_name = secret.name.as_ref().unwrap();
+
+            if secret.external.unwrap_or_default() {
+                eprintln!(
+                    "{} External secret \"{secret_name}\" must exist as the Kubernetes secret \"{}\"",
+                    *STYLED_WARNING,
+                    object_name(secret_name)
+                );
+                continue;
+            }
+
+            let contents = if let Some(environment) = &secret.environment {
+                env::var(environment)
+                    .map_err(|_| anyhow!("Environment variable \"{environment}\" not set"))?
+                    .into_bytes()
+            } else if let Some(file) = &secret.file {
+                fs::read(file)
+                    .map_err(|_| anyhow!("Secret file \"{}\" not found", file.display()))?
+            } else {
+                Vec::new()
+            };
+
+            self.push(
+                "Secret",
+                object_name(secret_name),
+                Body::Data {
+                    data: IndexMap::from([(key.clone(), base64::encode(contents))]),
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    fn persistent_volume_claims(&mut self) {
+        for volume in self.file.volumes.values() {
+            if volume.external.unwrap_or_default() {
+                continue;
+            }
+
+            if volume.driver.is_some() || !volume.driver_opts.is_empty() {
+                eprintln!(
+                    "{} Driver options of volume \"{}\" are not represented",
+                    *STYLED_WARNING,
+                    volume.name.as_ref().unwrap()
+                );
+            }
+
+            self.push(
+                "PersistentVolumeClaim",
+                object_name(volume.name.as_ref().unwrap()),
+                Body::Spec {
+                    spec: Spec::PersistentVolumeClaim(PersistentVolumeClaimSpec {
+                        access_modes: vec!["ReadWriteOnce"],
+                        resources: ResourceRequirements {
+                            requests: IndexMap::from([("storage", String::from("1Gi"))]),
+                            ..ResourceRequirements::default()
+                        },
+                    }),
+                },
+            );
+        }
+    }
+
+    fn config_map(&mut self, service_name: &str, env_files: &[impl AsRef<Path>]) -> Result<String> {
+        let config_map_name = object_name(&format!("{}-{service_name}-env", self.name));
+        let data = env_files
+            .iter()
+            .map(|env_file| {
+                let env_file = env_file.as_ref();
+
+                dotenvy::from_path_iter(env_file)
+                    .map_err(|_| anyhow!("{} not found", env_file.display()))?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| anyhow!("Invalid {}: {err}", env_file.display()))
+            })
+            .flatten_ok()
+            .collect::<Result<IndexMap<_, _>>>()?;
+
+        self.push("ConfigMap", config_map_name.clone(), Body::Data { data });
+
+        Ok(config_map_name)
+    }
+
+    fn container(
+        &mut self,
+        service_name: &str,
+        service: &Service,
+        volumes: &mut IndexMap<String, VolumeSource>,
+    ) -> Result<Container> {
+        let unsupported = serde_yaml::to_value(service)?
+            .as_mapping()
+            .into_iter()
+            .flat_map(|mapping| mapping.keys())
+            .filter_map(|key| key.as_str())
+            .filter(|key| !SUPPORTED.contains(key))
+            .map(|key| format!("services.{service_name}.{key}"))
+            .collect::<Vec<_>>();
+
+        if !unsupported.is_empty() {
+            eprintln!(
+                "{} Properties not represented in Kubernetes: {}",
+                *STYLED_WARNING,
+                unsupported.join(", ")
+            );
+        }
+
+        let mut container = Container {
+            name: object_name(service_name),
+            image: service.image.clone(),
+            image_pull_policy: service
+                .pull_policy
+                .as_ref()
+                .map(|pull_policy| match pull_policy {
+                    PullPolicy::Always | PullPolicy::Newer => "Always",
+                    PullPolicy::Never => "Never",
+                    PullPolicy::Missing => "IfNotPresent",
+                }),
+            command: service.entrypoint.clone(),
+            args: service.command.clone(),
+            working_dir: service
+                .working_dir
+                .as_ref()
+                .map(|working_dir| working_dir.to_string_lossy().to_string()),
+            stdin: service.stdin_open,
+            tty: service.tty,
+            ..Container::default()
+        };
+
+        for (key, value) in &service.environment {
+            if let Some(value) = value.clone().or_else(|| env::var(key).ok()) {
+                container.env.push(EnvVar {
+                    name: key.clone(),
+                    value: Some(value),
+                    value_from: None,
+                });
+            }
+        }
+
+        if !service.env_file.is_empty() {
+            container.env_from.push(EnvFromSource {
+                config_map_ref: LocalObjectReference {
+                    name: self.config_map(service_name, &service.env_file)?,
+                },
+            });
+        }
+
+        for port in &service.ports {
+            let (Ok(container_port), Ok(host_port)) = (
+                port.target.parse::<u16>(),
+                port.published
+                    .as_ref()
+                    .map(|port| port.parse::<u16>())
+                    .transpose(),
+            ) else {
+                eprintln!(
+                    "{} Port range \"{port}\" of service \"{service_name}\" is not represented",
+                    *STYLED_WARNING
+                );
+                continue;
+            };
+
+            container.ports.push(ContainerPort {
+                container_port,
+                host_port,
+                host_ip: port.host_ip.clone(),
+                protocol: port.protocol.to_uppercase(),
+            });
+        }
+
+        for volume in &service.volumes {
+            let target = volume.target.to_string_lossy().to_string();
+            let (name, source) = match &volume.r#type {
+                ServiceVolumeType::Volume(Some(source)) => {
+                    let name = object_name(self.file.volumes[source].name.as_ref().unwrap());
+
+                    (
+                        name.clone(),
+                        VolumeSource::PersistentVolumeClaim { claim_name: name },
+                    )
+                }
+                ServiceVolumeType::Bind(source) => (
+                    object_name(&format!("{service_name}-{target}")),
+                    VolumeSource::HostPath {
+                        path: source.to_string_lossy().to_string(),
+                        r#type: volume
+                            .bind
+                            .as_ref()
+                            .and_then(|bind| bind.create_host_path)
+                            .unwrap_or_default()
+                            .then_some("DirectoryOrCreate"),
+                    },
+                ),
+                ServiceVolumeType::Volume(None) => (
+                    object_name(&format!("{service_name}-{target}")),
+                    VolumeSource::EmptyDir { medium: None },
+                ),
+                ServiceVolumeType::Tmpfs => (
+                    object_name(&format!("{service_name}-{target}")),
+                    VolumeSource::EmptyDir {
+                        medium: Some("Memory"),
+                    },
+                ),
+            };
+
+            volumes.insert(name.clone(), source);
+            container.volume_mounts.push(VolumeMount {
+                name,
+                mount_path: target,
+                sub_path: None,
+                read_only: volume.read_only,
+            });
+        }
+
+        for tmpfs in &service.tmpfs {
+            let target = tmpfs.to_string_lossy().to_string();
+            let name = object_name(&format!("{service_name}-{target}"));
+
+            volumes.insert(
+                name.clone(),
+                VolumeSource::EmptyDir {
+                    medium: Some("Memory"),
+                },
+            );
+            container.volume_mounts.push(VolumeMount {
+                name,
+                mount_path: target,
+                sub_path: None,
+                read_only: None,
+            });
+        }
+
+        for secret in &service.secrets {
+            let secret_name = object_name(self.file.secrets[&secret.source].name.as_ref().unwrap());
+
+            if let Some(FileReferenceType::Env) = secret.r#type {
+                container.env.push(EnvVar {
+                    name: secret
+                        .target
+                        .clone()
+                        .unwrap_or_else(|| secret.source.clone()),
+                    value: None,
+                    value_from: Some(EnvVarSource {
+                        secret_key_ref: KeySelector {
+                            name: secret_name,
+                            key: secret.source.clone(),
+                        },
+                    }),
+                });
+            } else {
+                let target = secret
+                    .target
+                    .clone()
+                    .unwrap_or_else(|| secret.source.clone());
+                let name = object_name(&format!("secret-{}", secret.source));
+
+                volumes.insert(
+                    name.clone(),
+                    VolumeSource::Secret {
+                        secret_name,
+                        default_mode: secret.mode,
+                    },
+                );
+                container.volume_mounts.push(VolumeMount {
+                    name,
+                    mount_path: if target.starts_with('/') {
+                        target
+                    } else {
+                        format!("/run/secrets/{target}")
+                    },
+                    sub_path: Some(secret.source.clone()),
+                    read_only: Some(true),
+                });
+            }
+        }
+
+        if let Some(healthcheck) = service
+            .healthcheck
+            .as_ref()
+            .filter(|healthcheck| !healthcheck.disable.unwrap_or_default())
+        {
+            let command = match healthcheck.test.split_first() {
+                Some((test, command)) if test == "CMD" => Some(command.to_vec()),
+                Some((test, command)) if test == "CMD-SHELL" => Some(vec![
+                    String::from("/bin/sh"),
+                    String::from("-c"),
+                    command.join(" "),
+                ]),
+                Some((test, _)) if test == "NONE" => None,
+                Some(_) => Some(vec![
+                    String::from("/bin/sh"),
+                    String::from("-c"),
+                    shell_words::join(&healthcheck.test),
+                ]),
+                None => None,
+            };
+
+            container.liveness_probe = command.map(|command| Probe {
+                exec: ExecAction { command },
+                period_seconds: healthcheck.interval.map(|interval| interval.as_secs()),
+                timeout_seconds: healthcheck.timeout.map(|timeout| timeout.as_secs()),
+                initial_delay_seconds: healthcheck
+                    .start_period
+                    .map(|start_period| start_period.as_secs()),
+                failure_threshold: healthcheck.retries,
+            });
+        }
+
+        let mut resources = ResourceRequirements::default();
+        let deploy_resources = service
+            .deploy
+            .as_ref()
+            .and_then(|deploy| deploy.resources.as_ref());
+        let limits = deploy_resources.and_then(|resources| resources.limits.as_ref());
+        let reservations = deploy_resources.and_then(|resources| resources.reservations.as_ref());
+
+        if let Some(cpus) = limits.and_then(|limits| limits.cpus).or(service.cpus) {
+            resources.limits.insert("cpu", cpus.to_string());
+        }
+
+        if let Some(memory) = limits
+            .and_then(|limits| limits.memory)
+            .or(service.mem_limit)
+        {
+            resources.limits.insert("memory", memory.to_string());
+        }
+
+        if let Some(cpus) = reservations.and_then(|reservations| reservations.cpus) {
+            resources.requests.insert("cpu", cpus.to_string());
+        }
+
+        if let Some(memory) = reservations
+            .and_then(|reservations| reservations.memory)
+            .or(service.mem_reservation)
+        {
+            resources.requests.insert("memory", memory.to_string());
+        }
+
+        if !resources.limits.is_empty() || !resources.requests.is_empty() {
+            container.resources = Some(resources);
+        }
+
+        let (run_as_user, run_as_group) = match service.user.as_ref().map(|user| {
+            let mut ids = user.splitn(2, ':').map(str::parse::<i64>);
+
+            (ids.next().transpose(), ids.next().transpose())
+        }) {
+            Some((Ok(user), Ok(group))) => (user, group),
+            Some(_) => {
+                eprintln!(
+                    "{} User of service \"{service_name}\" must be numeric to be represented",
+                    *STYLED_WARNING
+                );
+                (None, None)
+            }
+            None => (None, None),
+        };
+
+        let security_context = SecurityContext {
+            privileged: service.privileged,
+            read_only_root_filesystem: service.read_only,
+            run_as_user,
+            run_as_group,
+            capabilities: if service.cap_add.is_empty() && service.cap_drop.is_empty() {
+                None
+            } else {
+                Some(Capabilities {
+                    add: service.cap_add.clone(),
+                    drop: service.cap_drop.clone(),
+                })
+            },
+        };
+
+        if security_context.privileged.is_some()
+            || security_context.read_only_root_filesystem.is_some()
+            || security_context.run_as_user.is_some()
+            || security_context.capabilities.is_some()
+        {
+            container.security_context = Some(security_context);
+        }
+
+        Ok(container)
+    }
+
+    fn pod_spec<'b>(
+        &mut self,
+        services: impl IntoIterator<Item = (&'b String, &'b Service)>,
+    ) -> Result<PodSpec> {
+        let mut spec = PodSpec::default();
+        let mut volumes = IndexMap::new();
+        let mut host_aliases = IndexMap::<_, Vec<_>>::new();
+        let mut restart_policies = IndexSet::new();
+
+        for (service_name, service) in services {
+            spec.containers
+                .push(self.container(service_name, service, &mut volumes)?);
+
+            if let Some(hostname) = &service.hostname {
+                spec.hostname = Some(hostname.clone());
+            }
+
+            for (host, ip) in &service.extra_hosts {
+                host_aliases
+                    .entry(ip.clone())
+                    .or_default()
+                    .push(host.clone());
+            }
+
+            if let Some(restart) = &service.restart {
+                restart_policies.insert(restart_policy(restart));
+            }
+        }
+
+        if restart_policies.len() > 1 {
+            eprintln!(
+                "{} Restart policies differ between services, but apply to the whole pod",
+                *STYLED_WARNING
+            );
+        }
+
+        spec.restart_policy = restart_policies.pop();
+        spec.host_aliases = host_aliases
+            .into_iter()
+            .map(|(ip, hostnames)| HostAlias { ip, hostnames })
+            .collect();
+        spec.volumes = volumes
+            .into_iter()
+            .map(|(name, source)| Volume { name, source })
+            .collect();
+
+        Ok(spec)
+    }
+
+    fn pod(&mut self) -> Result<()> {
+        let file = self.file;
+
+        for (service_name, service) in &file.services {
+            if replicas(service) > 1 {
+                eprintln!(
+                    "{} Service \"{service_name}\" is scaled, only one container is generated",
+                    *STYLED_WARNING
+                );
+            }
+        }
+
+        let spec = self.pod_spec(&file.services)?;
+        let mut metadata = ObjectMeta {
+            name: Some(object_name(self.name)),
+            labels: labels(self.name),
+        };
+
+        for service in file.services.values() {
+            metadata.labels.extend(service.labels.clone());
+        }
+
+        self.objects.push(Object {
+            api_version: "v1",
+            kind: "Pod",
+            metadata,
+            body: Body::Spec {
+                spec: Spec::Pod(spec),
+            },
+        });
+
+        Ok(())
+    }
+
+    fn deployments(&mut self) -> Result<()> {
+        let file = self.file;
+
+        for (service_name, service) in &file.services {
+            let mut spec = self.pod_spec([(service_name, service)])?;
+
+            if spec.restart_policy.replace("Always").unwrap_or("Always") != "Always" {
+                eprintln!(
+                    "{} Deployments always restart containers, restart policy of service \"{service_name}\" is not represented",
+                    *STYLED_WARNING
+                );
+            }
+
+            let mut match_labels = labels(self.name);
+            match_labels.shift_remove("io.podman.compose.version");
+            match_labels.insert(
+                String::from("io.podman.compose.service"),
+                service_name.clone(),
+            );
+
+            // Podman copies the labels of the pod onto its containers
+            let mut template_labels = labels(self.name);
+            template_labels.extend(match_labels.clone());
+            template_labels.extend(
+                [("oneoff", "false"), ("container-number", "1")]
+                    .into_iter()
+                    .map(|(key, value)| (format!("io.podman.compose.{key}"), value.to_string())),
+            );
+            template_labels.extend(service.labels.clone());
+
+            self.push(
+                "Deployment",
+                object_name(&format!("{}-{service_name}", self.name)),
+                Body::Spec {
+                    spec: Spec::Deployment(DeploymentSpec {
+                        replicas: replicas(service),
+                        selector: LabelSelector { match_labels },
+                        template: PodTemplateSpec {
+                            metadata: ObjectMeta {
+                                name: None,
+                                labels: template_labels,
+                            },
+                            spec,
+                        },
+                    }),
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders the project as Kubernetes objects, in a multi-document YAML
+pub(crate) fn convert(file: &Compose, kind: Kind) -> Result<String> {
+    let mut converter = Converter::new(file);
+
+    if file
+        .services
+        .values()
+        .any(|service| !service.depends_on.is_empty())
+    {
+        eprintln!(
+            "{} Kubernetes doesn't order the startup of containers, dependencies between services are ignored",
+            *STYLED_WARNING
+        );
+    }
+
+    converter.secrets()?;
+    converter.persistent_volume_claims();
+
+    match kind {
+        Kind::Pod => converter.pod()?,
+        Kind::Deployment => converter.deployments()?,
+    }
+
+    Ok(converter
+        .objects
+        .iter()
+        .map(serde_yaml::to_string)
+        .collect::<Result<Vec<_>, _>>()?
+        .join("---\n"))
+}
@@ -0,0 +1,428 @@
+use std::fmt::{self, Display, Formatter};
+
+use clap::crate_version;
+use indexmap::IndexMap;
+
+use crate::compose::{
+    types::{Compose, Condition, FileReference, Service, ServiceVolume, ServiceVolumeType},
+    STYLED_WARNING,
+};
+
+/// Podman flags that don't take a value
+const FLAGS: [&str; 9] = [
+    "--init",
+    "--interactive",
+    "--internal",
+    "--ipv6",
+    "--no-healthcheck",
+    "--oom-kill-disable",
+    "--privileged",
+    "--read-only",
+    "--tty",
+];
+
+const CONTAINER_KEYS: [(&str, &str, &str); 22] = [
+    ("--add-host", "Container", "AddHost"),
+    ("--cap-add", "Container", "AddCapability"),
+    ("--cap-drop", "Container", "DropCapability"),
+    ("--device", "Container", "AddDevice"),
+    ("--dns", "Container", "DNS"),
+    ("--dns-option", "Container", "DNSOption"),
+    ("--dns-search", "Container", "DNSSearch"),
+    ("--env", "Container", "Environment"),
+    ("--env-file", "Container", "EnvironmentFile"),
+    ("--expose", "Container", "ExposeHostPort"),
+    ("--health-cmd", "Container", "HealthCmd"),
+    ("--health-interval", "Container", "HealthInterval"),
+    ("--health-retries", "Container", "HealthRetries"),
+    ("--health-start-period", "Container", "HealthStartPeriod"),
+    ("--health-timeout", "Container", "HealthTimeout"),
+    ("--hostname", "Container", "HostName"),
+    ("--init", "Container", "RunInit"),
+    ("--label", "Container", "Label"),
+    ("--publish", "Container", "PublishPort"),
+    ("--read-only", "Container", "ReadOnly"),
+    ("--restart", "Service", "Restart"),
+    ("--user", "Container", "User"),
+];
+
+const NETWORK_KEYS: [(&str, &str, &str); 9] = [
+    ("--driver", "Network", "Driver"),
+    ("--gateway", "Network", "Gateway"),
+    ("--internal", "Network", "Internal"),
+    ("--ip-range", "Network", "IPRange"),
+    ("--ipam-driver", "Network", "IPAMDriver"),
+    ("--ipv6", "Network", "IPv6"),
+    ("--label", "Network", "Label"),
+    ("--opt", "Network", "Options"),
+    ("--subnet", "Network", "Subnet"),
+];
+
+const VOLUME_KEYS: [(&str, &str, &str); 3] = [
+    ("--driver", "Volume", "Driver"),
+    ("--label", "Volume", "Label"),
+    ("--opt", "Volume", "Options"),
+];
+
+const POD_KEYS: [(&str, &str, &str); 1] = [("--network", "Pod", "Network")];
+
+/// A systemd unit file, made of sections of `Key=Value` entries
+#[derive(Default, Debug)]
+pub(crate) struct Unit(IndexMap<&'static str, Vec<(&'static str, String)>>);
+
+impl Unit {
+    fn add(&mut self, section: &'static str, key: &'static str, value: impl Into<String>) {
+        self.0.entry(section).or_default().push((key, value.into()));
+    }
+
+    /// Maps the podman flags onto Quadlet keys, passing the remaining ones through `PodmanArgs=`,
+    /// and returns the positional arguments
+    fn add_args(
+        &mut self,
+        section: &'static str,
+        args: Vec<String>,
+        keys: &[(&str, &'static str, &'static str)],
+    ) -> Vec<String> {
+        let mut podman_args = Vec::new();
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !positional.is_empty() || !arg.starts_with("--") {
+                positional.push(arg);
+                continue;
+            }
+
+            let value = if FLAGS.contains(&arg.as_str()) || arg.contains('=') {
+                None
+            } else {
+                args.next()
+            };
+
+            match keys.iter().find(|(flag, _, _)| *flag == arg) {
+                Some((_, section, key)) => {
+                    let value = match (*key, value) {
+                        // systemd has no notion of a manually stopped service
+                        ("Restart", Some(value)) if value == "unless-stopped" => {
+                            String::from("always")
+                        }
+                        ("Environment" | "Label", Some(value))
+                            if value.contains(char::is_whitespace) =>
+                        {
+                            format!("\"{}\"", value.replace('\\', r"\\").replace('"', r#"\""#))
+                        }
+                        (_, Some(value)) => value,
+                        (_, None) => String::from("true"),
+                    };
+
+                    self.add(section, key, value);
+                }
+                None => podman_args.extend([arg].into_iter().chain(value)),
+            }
+        }
+
+        if !podman_args.is_empty() {
+            self.add(section, "PodmanArgs", shell_words::join(podman_args));
+        }
+
+        positional
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, (section, entries)) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "[{section}]")?;
+
+            for (key, value) in entries {
+                writeln!(f, "{key}={value}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn labels(name: &str, labels: &[(&str, &str)]) -> Vec<String> {
+    [("version", crate_version!()), ("project", name)]
+        .iter()
+        .chain(labels)
+        .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
+        .collect()
+}
+
+fn container_name(name: &str, service_name: &str, service: &Service) -> String {
+    service
+        .container_name
+        .clone()
+        .unwrap_or_else(|| format!("{name}_{service_name}_1"))
+}
+
+fn container(file: &Compose, service_name: &str, service: &Service) -> Unit {
+    let name = file.name.as_ref().unwrap();
+    let mut unit = Unit::default();
+    let (global_args, args) = service.to_args();
+
+    unit.add(
+        "Unit",
+        "Description",
+        format!("Service {service_name} of project {name}"),
+    );
+
+    for (dependency_name, dependency) in &service.depends_on {
+        if dependency.condition != Condition::Started {
+            eprintln!(
+                "{} Service \"{service_name}\" can only wait for \"{dependency_name}\" to be started",
+                *STYLED_WARNING
+            );
+        }
+
+        let dependency = format!(
+            "{}.service",
+            container_name(name, dependency_name, &file.services[dependency_name])
+        );
+
+        unit.add("Unit", "Requires", &dependency);
+        unit.add("Unit", "After", dependency);
+    }
+
+    unit.add(
+        "Container",
+        "ContainerName",
+        container_name(name, service_name, service),
+    );
+
+    if file.has_pod() {
+        unit.add("Container", "Pod", format!("{name}.pod"));
+    }
+
+    for label in labels(
+        name,
+        &[
+            ("oneoff", "false"),
+            ("service", service_name),
+            ("container-number", "1"),
+        ],
+    ) {
+        unit.add("Container", "Label", label);
+    }
+
+    if let Some(pull_policy) = &service.pull_policy {
+        unit.add("Container", "Pull", pull_policy.to_string());
+    }
+
+    for (network_name, network) in &service.networks {
+        let reference = &file.networks[network_name];
+        let mut network = network
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        if let Some(mac_address) = service.mac_address.as_ref() {
+            if network.is_empty() {
+                network = format!(":mac={mac_address}");
+            } else {
+                network = format!("{network},mac={mac_address}");
+            }
+        }
+
+        unit.add(
+            "Container",
+            "Network",
+            if reference.external.unwrap_or_default() {
+                format!("{}{network}", reference.name.as_ref().unwrap())
+            } else {
+                format!("{}.network{network}", reference.name.as_ref().unwrap())
+            },
+        );
+    }
+
+    for volume in &service.volumes {
+        match &volume.r#type {
+            ServiceVolumeType::Volume(Some(source)) => {
+                let reference = &file.volumes[source];
+                let source = reference.name.clone().unwrap();
+
+                unit.add(
+                    "Container",
+                    "Volume",
+                    ServiceVolume {
+                        r#type: ServiceVolumeType::Volume(Some(
+                            if reference.external.unwrap_or_default() {
+                                source
+                            } else {
+                                format!("{source}.volume")
+                            },
+                        )),
+                        ..volume.clone()
+                    }
+                    .to_string(),
+                );
+            }
+            ServiceVolumeType::Volume(None) | ServiceVolumeType::Bind(_) => {
+                unit.add("Container", "Volume", volume.to_string());
+            }
+            ServiceVolumeType::Tmpfs => {
+                unit.add("Container", "Tmpfs", volume.to_string());
+            }
+        }
+    }
+
+    for secret in &service.secrets {
+        unit.add(
+            "Container",
+            "Secret",
+            FileReference {
+                source: file.secrets[&secret.source].name.clone().unwrap(),
+                ..secret.clone()
+            }
+            .to_string(),
+        );
+    }
+
+    let mut positional = unit
+        .add_args("Container", args, &CONTAINER_KEYS)
+        .into_iter();
+
+    if let Some(image) = positional.next() {
+        unit.add("Container", "Image", image);
+    }
+
+    let command = positional.collect::<Vec<_>>();
+
+    if !command.is_empty() {
+        unit.add("Container", "Exec", shell_words::join(command));
+    }
+
+    if !global_args.is_empty() {
+        unit.add("Container", "GlobalArgs", shell_words::join(global_args));
+    }
+
+    unit.add("Install", "WantedBy", "default.target");
+
+    unit
+}
+
+/// Generates the Quadlet units of the project, as pairs of file name and contents
+pub(crate) fn convert(file: &Compose) -> Vec<(String, String)> {
+    let name = file.name.as_ref().unwrap();
+    let mut units = Vec::new();
+
+    if file.has_pod() {
+        let mut unit = Unit::default();
+
+        unit.add("Pod", "PodName", name);
+        unit.add_args(
+            "Pod",
+            file.x_podman
+                .clone()
+                .unwrap_or_default()
+                .to_args()
+                .into_iter()
+                .chain(
+                    labels(name, &[])
+                        .into_iter()
+                        .flat_map(|label| [String::from("--label"), label]),
+                )
+                .collect(),
+            &POD_KEYS,
+        );
+        units.push((format!("{name}.pod"), unit));
+    }
+
+    for network in file.networks.values() {
+        let network_name = network.name.as_ref().unwrap();
+
+        if network.external.unwrap_or_default() {
+            eprintln!(
+                "{} External network \"{network_name}\" must exist before the units are started",
+                *STYLED_WARNING
+            );
+            continue;
+        }
+
+        let mut unit = Unit::default();
+
+        for label in labels(name, &[("network", network_name)]) {
+            unit.add("Network", "Label", label);
+        }
+
+        for network_name in unit.add_args("Network", network.to_args(), &NETWORK_KEYS) {
+            unit.add("Network", "NetworkName", network_name);
+        }
+
+        units.push((format!("{network_name}.network"), unit));
+    }
+
+    for volume in file.volumes.values() {
+        let volume_name = volume.name.as_ref().unwrap();
+
+        if volume.external.unwrap_or_default() {
+            eprintln!(
+                "{} External volume \"{volume_name}\" must exist before the units are started",
+                *STYLED_WARNING
+            );
+            continue;
+        }
+
+        let mut unit = Unit::default();
+
+        for label in labels(name, &[("volume", volume_name)]) {
+            unit.add("Volume", "Label", label);
+        }
+
+        for volume_name in unit.add_args("Volume", volume.to_args(), &VOLUME_KEYS) {
+            unit.add("Volume", "VolumeName", volume_name);
+        }
+
+        units.push((format!("{volume_name}.volume"), unit));
+    }
+
+    for secret in file.secrets.values() {
+        if !secret.external.unwrap_or_default() {
+            eprintln!(
+                "{} Quadlet can't create secrets, run `podman secret create {}` before the units are started",
+                *STYLED_WARNING,
+                shell_words::join(secret.to_args())
+            );
+        }
+    }
+
+    for (service_name, service) in &file.services {
+        if service.image.is_none() {
+            eprintln!(
+                "{} Service \"{service_name}\" has no image, build it before the units are started",
+                *STYLED_WARNING
+            );
+            continue;
+        }
+
+        if service
+            .deploy
+            .as_ref()
+            .and_then(|deploy| deploy.replicas)
+            .or(service.scale)
+            .unwrap_or(1)
+            > 1
+        {
+            eprintln!(
+                "{} Service \"{service_name}\" is scaled, only one container is generated",
+                *STYLED_WARNING
+            );
+        }
+
+        units.push((
+            format!("{}.container", container_name(name, service_name, service)),
+            container(file, service_name, service),
+        ));
+    }
+
+    units
+        .into_iter()
+        .map(|(file_name, unit)| (file_name, unit.to_string()))
+        .collect()
+}
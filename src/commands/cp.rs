@@ -7,13 +7,15 @@ use crate::{
     utils::parse_container_path,
 };
 
-/// Copy files/folders between a service container and the local filesystem
+/// Copy files/folders between service containers and the local filesystem
 #[derive(clap::Args, Debug)]
 #[command(next_display_order = None)]
 pub(crate) struct Args {
+    /// SERVICE:SRC_PATH, SRC_PATH or - to read a tar archive from stdin (glob patterns are allowed in SRC_PATH of a service)
     #[arg(value_parser = parse_container_path::<String, String>)]
     source: (Option<String>, String),
 
+    /// SERVICE:DEST_PATH, DEST_PATH or - to write a tar archive to stdout
     #[arg(value_parser = parse_container_path::<String, String>)]
     destination: (Option<String>, String),
 
@@ -26,10 +28,35 @@ pub(crate) struct Args {
     archive: bool,
 }
 
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+async fn expand_glob(podman: &Podman, container: &str, pattern: &str) -> Result<Vec<String>> {
+    let output = podman
+        .force_run([
+            "exec",
+            container,
+            "sh",
+            "-c",
+            r#"IFS=; for path in $1; do [ -e "$path" ] && printf '%s\n' "$path"; done; true"#,
+            "sh",
+            pattern,
+        ])
+        .await?;
+
+    Ok(output.lines().map(ToString::to_string).collect())
+}
+
 pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<()> {
-    match (&args.source.0, &args.destination.0) {
-        (Some(_), Some(_)) => bail!("Copying between services is not supported"),
-        (None, None) => bail!("Unknown copy direction"),
+    match (&args.source, &args.destination) {
+        ((None, _), (None, _)) => bail!("Unknown copy direction"),
+        (_, (Some(_), destination)) if is_glob(destination) => {
+            bail!("Glob patterns are only supported in the source path")
+        }
+        ((None, source), _) if is_glob(source) => {
+            bail!("Glob patterns are only supported in the path of a service")
+        }
         _ => {}
     }
 
@@ -40,7 +67,10 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
             "--format",
             "json",
             "--filter",
-            &format!("pod={}", file.name.as_ref().unwrap()),
+            &format!(
+                "label=io.podman.compose.project={}",
+                file.name.as_ref().unwrap()
+            ),
         ])
         .await?;
     let containers = serde_json::from_str::<Vec<Container>>(&output)?
@@ -73,11 +103,12 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
         })
         .into_group_map();
 
-    let [source, destination] = [args.source.0, args.destination.0].map(|service| {
+    let [source, destination] = [&args.source.0, &args.destination.0].map(|service| {
         service
+            .as_ref()
             .map(|service| {
                 containers
-                    .get(&service)
+                    .get(service)
                     .ok_or_else(|| anyhow!("No container found for service \"{service}\""))
                     .and_then(|containers| {
                         containers
@@ -93,36 +124,78 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
             })
             .transpose()
     });
+    let (source, destination) = (source?, destination?);
 
-    podman
-        .run(
-            ["cp"]
-                .into_iter()
-                .chain(if args.archive {
-                    vec!["--archive"]
-                } else {
-                    vec![]
-                })
-                .chain([
-                    format!(
-                        "{}{}",
-                        source?
-                            .map(|container| format!("{container}:"))
-                            .unwrap_or_default(),
-                        args.source.1
-                    )
-                    .as_ref(),
-                    format!(
-                        "{}{}",
-                        destination?
-                            .map(|container| format!("{container}:"))
-                            .unwrap_or_default(),
-                        args.destination.1
-                    )
-                    .as_ref(),
-                ]),
-        )
-        .await?;
+    let paths = match source {
+        Some(container) if is_glob(&args.source.1) => {
+            let paths = expand_glob(podman, container, &args.source.1).await?;
+
+            if paths.is_empty() {
+                bail!("No such file or directory: \"{}\"", args.source.1);
+            }
+
+            if paths.len() > 1 && args.destination.1 == "-" {
+                bail!("Glob patterns matching multiple paths cannot be written to stdout");
+            }
+
+            paths
+        }
+        _ => vec![args.source.1.clone()],
+    };
+    let archive = if args.archive {
+        vec!["--archive"]
+    } else {
+        vec![]
+    };
+    let destination_path = format!(
+        "{}{}",
+        destination
+            .map(|container| format!("{container}:"))
+            .unwrap_or_default(),
+        args.destination.1
+    );
+
+    for path in paths {
+        let source_path = format!(
+            "{}{path}",
+            source
+                .map(|container| format!("{container}:"))
+                .unwrap_or_default()
+        );
+
+        if source.is_some() && destination.is_some() {
+            podman
+                .pipe(
+                    ["cp"]
+                        .into_iter()
+                        .chain(archive.iter().copied())
+                        .chain([source_path.as_ref(), "-"]),
+                    ["cp"]
+                        .into_iter()
+                        .chain(archive.iter().copied())
+                        .chain(["-", destination_path.as_ref()]),
+                )
+                .await?;
+        } else if path == "-" || args.destination.1 == "-" {
+            podman
+                .attach(
+                    ["cp"]
+                        .into_iter()
+                        .chain(archive.iter().copied())
+                        .chain([source_path.as_ref(), destination_path.as_ref()]),
+                )
+                .await?;
+        } else {
+            podman
+                .run(
+                    ["cp"]
+                        .into_iter()
+                        .chain(archive.iter().copied())
+                        .chain([source_path.as_ref(), destination_path.as_ref()]),
+                )
+                .await?;
+        }
+    }
 
     Ok(())
 }
@@ -5,7 +5,7 @@ use std::{
     fs,
 };
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::{crate_version, ValueEnum};
 use futures::{stream::FuturesUnordered, try_join, StreamExt, TryStreamExt};
 use heck::AsKebabCase;
@@ -16,14 +16,31 @@ use tokio::sync::{broadcast, Barrier};
 use tokio_stream::wrappers::BroadcastStream;
 
 use crate::{
-    commands::down,
-    compose::types::{Compose, FileReference, ServiceVolume, ServiceVolumeType},
+    commands::{
+        down::{self, remove_secrets},
+        rm::{self, remove_containers},
+        stop::{self, stop_containers},
+        up,
+    },
+    compose::{
+        types::{self, Backend, Compose, ServiceVolumeType},
+        STYLED_WARNING,
+    },
     config::Config,
-    podman::{types::Pod, Podman},
+    podman::{
+        plan::Resource,
+        spec::{
+            field_hashes, network_payload, pod_payload, volume_payload, ContainerSpec, Payload,
+        },
+        types::{Container, Pod, Secret},
+        Podman,
+    },
     progress::{Finish, Progress},
     utils::Digest,
 };
 
+mod plan;
+
 /// Creates containers for a service
 #[derive(clap::Args, Debug)]
 #[command(next_display_order = None)]
@@ -45,6 +62,10 @@ pub(crate) struct Args {
     /// Remove containers for services not defined in the Compose file
     #[arg(long)]
     pub(crate) remove_orphans: bool,
+
+    /// Show how the project would change, without applying anything
+    #[arg(long)]
+    pub(crate) plan: bool,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -61,6 +82,28 @@ impl Display for PullPolicy {
     }
 }
 
+/// Labels locating the Compose files of the project, which its pod and each of its containers carry
+fn project_labels(config: &Config) -> [(&'static str, String); 3] {
+    [
+        (
+            "project.working-dir",
+            config.project_directory.to_string_lossy().to_string(),
+        ),
+        (
+            "project.config-files",
+            config
+                .files
+                .iter()
+                .map(|file| file.to_string_lossy())
+                .join(","),
+        ),
+        (
+            "project.environment-file",
+            config.env_file.to_string_lossy().to_string(),
+        ),
+    ]
+}
+
 async fn create_pod(
     podman: &Podman,
     config: &Config,
@@ -69,45 +112,43 @@ async fn create_pod(
 ) -> Result<()> {
     let name = file.name.as_ref().unwrap();
 
-    if podman.force_run(["pod", "exists", name]).await.is_err() {
-        let pod_labels = [
-            (
-                "project.working-dir",
-                config.project_directory.to_string_lossy().as_ref(),
-            ),
-            (
-                "project.config-files",
-                &config
-                    .files
-                    .iter()
-                    .map(|file| file.to_string_lossy())
-                    .join(","),
-            ),
-            (
-                "project.environment-file",
-                config.env_file.to_string_lossy().as_ref(),
-            ),
-            ("config-hash", &file.digest()),
-        ]
-        .into_iter()
-        .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
-        .collect::<Vec<_>>();
+    if file.has_pod() && !podman.exists(Resource::Pod, name).await? {
+        let pod_labels = project_labels(config)
+            .into_iter()
+            .chain([("config-hash", file.digest())])
+            .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
+            .collect::<Vec<_>>();
+
+        let x_podman = file.x_podman.clone().unwrap_or_default();
+        let payload = pod_payload(
+            name,
+            &x_podman,
+            &labels
+                .iter()
+                .chain(&pod_labels)
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
 
         podman
-            .run(
-                ["pod", "create", "--share", "none"]
+            .create(
+                ["pod", "create"]
                     .into_iter()
+                    .chain(x_podman.to_args().iter().map(AsRef::as_ref))
                     .chain(labels.iter().flat_map(|label| ["--label", label]))
                     .chain(pod_labels.iter().flat_map(|label| ["--label", label]))
                     .chain([name.as_ref()]),
+                payload.map(Payload::Pod),
             )
             .await?;
+    } else if file.has_pod() {
+        podman.keep(Resource::Pod, name, "already exists");
     }
 
     Ok(())
 }
 
-async fn create_networks(
+pub(crate) async fn create_networks(
     podman: &Podman,
     progress: &Progress,
     file: &Compose,
@@ -119,7 +160,7 @@ async fn create_networks(
             let name = network.name.as_ref().unwrap();
             let spinner = progress.add_spinner(format!("Network {name}"), "Creating");
 
-            if podman.force_run(["network", "exists", name]).await.is_err() {
+            if !podman.exists(Resource::Network, name).await? {
                 if network.external.unwrap_or_default() {
                     bail!("External network \"{name}\" not found");
                 }
@@ -129,17 +170,28 @@ async fn create_networks(
                     .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
                     .collect::<Vec<_>>();
 
+                let payload = network_payload(
+                    network,
+                    &labels
+                        .iter()
+                        .chain(&network_labels)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                );
+
                 podman
-                    .run(
+                    .create(
                         ["network", "create"]
                             .into_iter()
                             .chain(labels.iter().flat_map(|label| ["--label", label]))
                             .chain(network_labels.iter().flat_map(|label| ["--label", label]))
                             .chain(network.to_args().iter().map(AsRef::as_ref)),
+                        payload.map(Payload::Network),
                     )
                     .await
                     .finish_with_message(spinner, "Created")?;
             } else {
+                podman.keep(Resource::Network, name, "already exists");
                 spinner.finish_with_message("Exists");
             }
 
@@ -163,7 +215,7 @@ async fn create_volumes(
             let name = volume.name.as_ref().unwrap();
             let spinner = progress.add_spinner(format!("Volume {name}"), "Creating");
 
-            if podman.force_run(["volume", "exists", name]).await.is_err() {
+            if !podman.exists(Resource::Volume, name).await? {
                 if volume.external.unwrap_or_default() {
                     bail!("External volume \"{name}\" not found");
                 }
@@ -173,17 +225,28 @@ async fn create_volumes(
                     .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
                     .collect::<Vec<_>>();
 
+                let payload = volume_payload(
+                    volume,
+                    &labels
+                        .iter()
+                        .chain(&volume_labels)
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                );
+
                 podman
-                    .run(
+                    .create(
                         ["volume", "create"]
                             .into_iter()
                             .chain(labels.iter().flat_map(|label| ["--label", label]))
                             .chain(volume_labels.iter().flat_map(|label| ["--label", label]))
                             .chain(volume.to_args().iter().map(AsRef::as_ref)),
+                        Some(Payload::Volume(payload)),
                     )
                     .await
                     .finish_with_message(spinner, "Created")?;
             } else {
+                podman.keep(Resource::Volume, name, "already exists");
                 spinner.finish_with_message("Exists");
             }
 
@@ -195,6 +258,185 @@ async fn create_volumes(
         .map(|_| ())
 }
 
+fn secret_content(secret: &types::Secret) -> Result<Option<Vec<u8>>> {
+    let name = secret.name.as_deref().unwrap_or_default();
+
+    Ok(Some(if let Some(environment) = &secret.environment {
+        env::var(environment)
+            .with_context(|| {
+                anyhow!("Environment variable \"{environment}\" of secret \"{name}\" not set")
+            })?
+            .into_bytes()
+    } else if let Some(file) = &secret.file {
+        fs::read(file).with_context(|| {
+            anyhow!("File \"{}\" of secret \"{name}\" not found", file.display())
+        })?
+    } else {
+        return Ok(None);
+    }))
+}
+
+/// Secrets among `existing` whose content differs from their source, by source and name, which
+/// are read back from podman so that nothing derived from their content is kept in their labels
+async fn changed_secrets<'a>(
+    podman: &Podman,
+    file: &'a Compose,
+    existing: &[String],
+) -> Result<Vec<(&'a String, &'a String)>> {
+    let mut changed = Vec::new();
+
+    for (source, secret) in file
+        .secrets
+        .iter()
+        .filter(|(_, secret)| !secret.external.unwrap_or_default())
+    {
+        let name = secret.name.as_ref().unwrap();
+
+        if !existing.contains(name) {
+            continue;
+        }
+
+        let Some(content) = secret_content(secret)? else {
+            continue;
+        };
+        let data = podman
+            .force_run([
+                "secret",
+                "inspect",
+                "--showsecret",
+                "--format",
+                "json",
+                name,
+            ])
+            .await
+            .ok()
+            .and_then(|output| serde_json::from_str::<VecDeque<Secret>>(&output).ok())
+            .and_then(|mut secrets| secrets.pop_front())
+            .and_then(|secret| secret.secret_data);
+
+        match data {
+            Some(data) if data.as_bytes() != content => changed.push((source, name)),
+            Some(_) => {}
+            None => eprintln!(
+                "{} Changes to secret \"{name}\" are not detected, as reading it back needs Podman 4.5 or above",
+                *STYLED_WARNING
+            ),
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Removes secrets whose content has changed, along with the containers that consume them
+async fn replace_secrets(podman: &Podman, config: &Config, file: &Compose) -> Result<()> {
+    let name = file.name.as_ref().unwrap();
+    if file
+        .secrets
+        .values()
+        .all(|secret| secret.external.unwrap_or_default())
+    {
+        return Ok(());
+    }
+
+    let existing = serde_json::from_str::<Option<Vec<Secret>>>(
+        &podman
+            .force_run(["secret", "ls", "--format", "json"])
+            .await?,
+    )?
+    .unwrap_or_default()
+    .into_iter()
+    .map(|secret| secret.spec.name)
+    .collect::<Vec<_>>();
+    let changed = changed_secrets(podman, file, &existing).await?;
+
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    let _reason = podman.reason("secret content changed");
+
+    let output = podman
+        .force_run([
+            "ps",
+            "--all",
+            "--format",
+            "json",
+            "--filter",
+            "label=io.podman.compose.oneoff=false",
+            "--filter",
+            &format!("label=io.podman.compose.project={name}"),
+        ])
+        .await?;
+    let containers = serde_json::from_str::<Vec<Container>>(&output)?
+        .into_iter()
+        .filter_map(|mut container| {
+            container
+                .labels
+                .and_then(|labels| labels.service)
+                .filter(|service| {
+                    file.services.get(service).is_some_and(|service| {
+                        service.secrets.iter().any(|secret| {
+                            changed.iter().any(|(source, _)| **source == secret.source)
+                        })
+                    })
+                })
+                .and_then(|service| container.names.pop_front().map(|name| (service, name)))
+        })
+        .into_group_map();
+
+    if !containers.is_empty() {
+        let progress = Progress::new(config);
+
+        stop_containers(
+            podman,
+            &progress,
+            file,
+            &containers,
+            stop::Args {
+                services: Vec::new(),
+                timeout: None,
+            },
+        )
+        .await?;
+
+        progress.finish();
+
+        let progress = Progress::new(config);
+
+        remove_containers(
+            podman,
+            &progress,
+            file,
+            &containers,
+            rm::Args {
+                services: Vec::new(),
+                force: true,
+                stop: false,
+                volumes: false,
+            },
+        )
+        .await?;
+
+        progress.finish();
+    }
+
+    let progress = Progress::new(config);
+
+    remove_secrets(
+        podman,
+        &progress,
+        &changed
+            .into_iter()
+            .map(|(_, secret)| secret.clone())
+            .collect::<Vec<_>>(),
+    )
+    .await?;
+
+    progress.finish();
+
+    Ok(())
+}
+
 async fn create_secrets(
     podman: &Podman,
     progress: &Progress,
@@ -207,15 +449,15 @@ async fn create_secrets(
             let name = secret.name.as_ref().unwrap();
             let spinner = progress.add_spinner(format!("Secret {name}"), "Creating");
 
-            if podman.force_run(["secret", "inspect", name]).await.is_err() {
+            if !podman.exists(Resource::Secret, name).await? {
                 if secret.external.unwrap_or_default() {
                     bail!("External secret \"{name}\" not found");
                 }
 
-                let secret_labels = [("secret", name)]
-                    .into_iter()
-                    .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
-                    .collect::<Vec<_>>();
+                // Fail with the source of the content rather than with podman's error
+                secret_content(secret)?;
+
+                let secret_labels = [format!("io.podman.compose.secret={name}")];
 
                 podman
                     .run(
@@ -228,6 +470,7 @@ async fn create_secrets(
                     .await
                     .finish_with_message(spinner, "Created")?;
             } else {
+                podman.keep(Resource::Secret, name, "already exists");
                 spinner.finish_with_message("Exists");
             }
 
@@ -241,13 +484,13 @@ async fn create_secrets(
 
 async fn create_containers(
     podman: &Podman,
-    config: &Config,
     progress: &Progress,
+    config: &Config,
     file: &Compose,
-    labels: &[String],
     args: Args,
 ) -> Result<()> {
     let project_name = file.name.as_ref().unwrap();
+    let project_labels = &project_labels(config);
     let mut dependencies = file
         .services
         .iter()
@@ -339,148 +582,56 @@ async fn create_containers(
                                 .try_concat()
                                 .await?;
 
-                            if podman
-                                .force_run(["container", "exists", &container_name])
-                                .await
-                                .is_err()
-                            {
-                                if !config.dry_run {
-                                    for volume in service.volumes.iter().filter(|volume| {
-                                        volume
-                                            .bind
-                                            .as_ref()
-                                            .and_then(|bind| bind.create_host_path)
-                                            .unwrap_or_default()
-                                    }) {
-                                        if let ServiceVolumeType::Bind(source) = &volume.r#type {
-                                            fs::create_dir_all(source).ok();
-                                        }
+                            if !podman.exists(Resource::Container, &container_name).await? {
+                                for volume in service.volumes.iter().filter(|volume| {
+                                    volume
+                                        .bind
+                                        .as_ref()
+                                        .and_then(|bind| bind.create_host_path)
+                                        .unwrap_or_default()
+                                }) {
+                                    if let ServiceVolumeType::Bind(source) = &volume.r#type {
+                                        podman.create_dir(source).ok();
                                     }
                                 }
 
-                                let container_labels = [
-                                    ("oneoff", "false"),
-                                    ("service", service_name),
-                                    ("container-number", &i.to_string()),
-                                ]
-                                .into_iter()
-                                .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
-                                .collect::<Vec<_>>();
-                                let pull_policy =
-                                    args.pull.as_ref().map(ToString::to_string).or_else(|| {
-                                        service.pull_policy.as_ref().map(ToString::to_string)
-                                    });
-
-                                let networks = service
-                                    .networks
-                                    .iter()
-                                    .map(|(name, network)| {
-                                        let name = file.networks[name].name.clone().unwrap();
-                                        let mut network = network
-                                            .as_ref()
-                                            .map(ToString::to_string)
-                                            .unwrap_or_default();
-
-                                        if let Some(mac_address) = service.mac_address.as_ref() {
-                                            if network.is_empty() {
-                                                network = format!(":mac={mac_address}");
-                                            } else {
-                                                network = format!("{network},mac={mac_address}");
-                                            }
-                                        }
-
-                                        format!("{name}{network}")
-                                    })
-                                    .collect::<Vec<_>>();
-                                let volumes = service
-                                    .volumes
-                                    .iter()
-                                    .flat_map(|volume| {
-                                        let volume = match &volume.r#type {
-                                            ServiceVolumeType::Volume(Some(source)) => {
-                                                ServiceVolume {
-                                                    r#type: ServiceVolumeType::Volume(
-                                                        file.volumes[source].name.clone(),
-                                                    ),
-                                                    ..volume.clone()
-                                                }
-                                            }
-                                            _ => volume.clone(),
-                                        };
-
-                                        [
-                                            String::from(match volume.r#type {
-                                                ServiceVolumeType::Volume(_)
-                                                | ServiceVolumeType::Bind(_) => "--volume",
-                                                ServiceVolumeType::Tmpfs => "--tmpfs",
-                                            }),
-                                            volume.to_string(),
-                                        ]
-                                    })
-                                    .collect::<Vec<_>>();
-                                let secrets = service
-                                    .secrets
-                                    .iter()
-                                    .map(|secret| {
-                                        FileReference {
-                                            source: file.secrets[&secret.source]
-                                                .name
-                                                .clone()
-                                                .unwrap(),
-                                            ..secret.clone()
-                                        }
-                                        .to_string()
-                                    })
-                                    .collect::<Vec<_>>();
-
-                                let (global_args, service_args) = service.to_args();
+                                let mut spec = ContainerSpec::new(
+                                    file,
+                                    service_name,
+                                    service,
+                                    container_name.clone(),
+                                );
+
+                                spec.requires = requirements;
+                                spec.label("oneoff", "false");
+                                spec.label("service", service_name);
+                                spec.label("container-number", i.to_string());
+
+                                for (key, value) in project_labels {
+                                    spec.label(key, value);
+                                }
+
+                                spec.label(
+                                    "config-fields",
+                                    field_hashes(service)
+                                        .iter()
+                                        .map(|(field, hash)| format!("{field}:{hash}"))
+                                        .join(","),
+                                );
+
+                                if let Some(pull) = &args.pull {
+                                    spec.pull = Some(pull.to_string());
+                                }
 
                                 podman
-                                    .run(
-                                        global_args
-                                            .iter()
-                                            .map(AsRef::as_ref)
-                                            .chain([
-                                                "create",
-                                                "--pod",
-                                                project_name,
-                                                "--name",
-                                                &container_name,
-                                                "--network-alias",
-                                                service_name,
-                                            ])
-                                            .chain(requirements.iter().flat_map(|requirement| {
-                                                ["--requires", requirement]
-                                            }))
-                                            .chain(
-                                                labels.iter().flat_map(|label| ["--label", label]),
-                                            )
-                                            .chain(
-                                                container_labels
-                                                    .iter()
-                                                    .flat_map(|label| ["--label", label]),
-                                            )
-                                            .chain(if let Some(pull_policy) = &pull_policy {
-                                                vec!["--pull", pull_policy]
-                                            } else {
-                                                vec![]
-                                            })
-                                            .chain(
-                                                networks
-                                                    .iter()
-                                                    .flat_map(|network| ["--network", network]),
-                                            )
-                                            .chain(volumes.iter().map(AsRef::as_ref))
-                                            .chain(
-                                                secrets
-                                                    .iter()
-                                                    .flat_map(|secret| ["--secret", secret]),
-                                            )
-                                            .chain(service_args.iter().map(AsRef::as_ref)),
+                                    .create(
+                                        spec.to_args("create"),
+                                        spec.to_payload().map(Payload::Container),
                                     )
                                     .await
                                     .finish_with_message(spinner, "Created")?;
                             } else {
+                                podman.keep(Resource::Container, &container_name, "already exists");
                                 spinner.finish_with_message("Exists");
                             }
 
@@ -512,6 +663,18 @@ pub(crate) async fn run(
     file: &Compose,
     config: &Config,
 ) -> Result<()> {
+    if let Backend::Kube = config.backend(file) {
+        if !args.services.is_empty() {
+            bail!("Selecting services is not supported by the kube backend");
+        }
+
+        if args.plan {
+            bail!("`--plan` is not supported by the kube backend");
+        }
+
+        return up::play(podman, file, config, false).await;
+    }
+
     let name = file.name.as_ref().unwrap();
     let labels = [("version", crate_version!()), ("project", name)]
         .into_iter()
@@ -532,12 +695,25 @@ pub(crate) async fn run(
         .pop_front()
         .and_then(|pod| pod.labels.and_then(|labels| labels.config_hash));
 
-    if args.force_recreate
-        || (!args.no_recreate
-            && config_hash
-                .map(|config_hash| config_hash != file.digest())
-                .unwrap_or_default())
+    let recreate = if args.force_recreate {
+        Some("recreation forced")
+    } else if !args.no_recreate
+        && config_hash
+            .map(|config_hash| config_hash != file.digest())
+            .unwrap_or_default()
     {
+        Some("configuration changed")
+    } else {
+        None
+    };
+
+    if args.plan {
+        return plan::show(podman, file, &args, recreate).await;
+    }
+
+    if let Some(reason) = recreate {
+        let _reason = podman.reason(reason);
+
         down::run(
             down::Args {
                 remove_orphans: args.remove_orphans,
@@ -552,6 +728,8 @@ pub(crate) async fn run(
         .await?;
     }
 
+    replace_secrets(podman, config, file).await?;
+
     let progress = Progress::new(config);
 
     try_join!(
@@ -572,10 +750,179 @@ pub(crate) async fn run(
     {
         let progress = Progress::new(config);
 
-        create_containers(podman, config, &progress, file, &labels, args).await?;
+        create_containers(podman, &progress, config, file, args).await?;
 
         progress.finish();
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::{podman::fake::Fake, progress::ProgressMode};
+
+    #[test]
+    fn parallel_limit() {
+        let file = serde_yaml::from_str::<Compose>(
+            r#"
+            name: proj
+            services:
+              a:
+                image: a
+              b:
+                image: b
+                scale: 3
+                depends_on: [a]
+              c:
+                image: c
+                depends_on: [b]
+            networks:
+              default:
+                name: proj_default
+            "#,
+        )
+        .unwrap();
+        let config = Config {
+            progress: ProgressMode::Quiet,
+            parallel_limit: Some(1),
+            ..Config::default()
+        };
+        let fake = Fake::default()
+            .script(&["ps"], Ok("[]"))
+            .delay(Duration::from_millis(10));
+        let invocations = fake.invocations();
+        let peak = fake.peak();
+        let podman = Podman::with_executor(Box::new(fake), &config);
+        let progress = Progress::new(&config);
+
+        // Containers wait for their dependencies without holding a permit, so a limit of 1 must not deadlock
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async {
+                timeout(
+                    Duration::from_secs(10),
+                    create_containers(
+                        &podman,
+                        &progress,
+                        &config,
+                        &file,
+                        Args {
+                            services: Vec::new(),
+                            pull: None,
+                            force_recreate: false,
+                            no_recreate: false,
+                            remove_orphans: false,
+                            plan: false,
+                        },
+                    ),
+                )
+                .await
+            })
+            .unwrap()
+            .unwrap();
+
+        let created = invocations
+            .borrow()
+            .iter()
+            .map(|(_, invocation)| invocation)
+            .filter(|invocation| invocation.starts_with("podman create "))
+            .filter_map(|invocation| {
+                invocation
+                    .split(' ')
+                    .skip_while(|arg| *arg != "--name")
+                    .nth(1)
+                    .map(String::from)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(peak.get(), 1);
+        assert_eq!(created.len(), 5);
+        assert_eq!(created.first().map(String::as_str), Some("proj_a_1"));
+        assert_eq!(created.last().map(String::as_str), Some("proj_c_1"));
+    }
+
+    #[test]
+    fn changed_secret() {
+        let file = serde_yaml::from_str::<Compose>(
+            r#"
+            name: proj
+            services: {}
+            secrets:
+              same:
+                name: proj_same
+                environment: SAME
+              changed:
+                name: proj_changed
+                environment: CHANGED
+              new:
+                name: proj_new
+                environment: NEW
+            "#,
+        )
+        .unwrap();
+        let config = Config {
+            progress: ProgressMode::Quiet,
+            ..Config::default()
+        };
+        let fake = Fake::default()
+            .script_exact(
+                &[
+                    "secret",
+                    "inspect",
+                    "--showsecret",
+                    "--format",
+                    "json",
+                    "proj_same",
+                ],
+                Ok(r#"[{"Spec":{"Name":"proj_same"},"SecretData":"same"}]"#),
+            )
+            .script_exact(
+                &[
+                    "secret",
+                    "inspect",
+                    "--showsecret",
+                    "--format",
+                    "json",
+                    "proj_changed",
+                ],
+                Ok(r#"[{"Spec":{"Name":"proj_changed"},"SecretData":"old"}]"#),
+            );
+        let invocations = fake.invocations();
+        let podman = Podman::with_executor(Box::new(fake), &config);
+
+        // The content is compared with the secret's own data, as nothing derived from it is stored
+        let changed = temp_env::with_vars(
+            [
+                ("SAME", Some("same")),
+                ("CHANGED", Some("new")),
+                ("NEW", Some("new")),
+            ],
+            || {
+                tokio::runtime::Runtime::new()
+                    .unwrap()
+                    .block_on(changed_secrets(
+                        &podman,
+                        &file,
+                        &[String::from("proj_same"), String::from("proj_changed")],
+                    ))
+                    .unwrap()
+                    .into_iter()
+                    .map(|(source, name)| (source.clone(), name.clone()))
+                    .collect::<Vec<_>>()
+            },
+        );
+
+        assert_eq!(
+            changed,
+            [(String::from("changed"), String::from("proj_changed"))]
+        );
+        assert_eq!(invocations.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,399 @@
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::Result;
+use console::style;
+use futures::try_join;
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+
+use super::{changed_secrets, Args};
+use crate::{
+    compose::types::Compose,
+    podman::{
+        spec::field_hashes,
+        types::{Container, Network, Secret, Volume},
+        Podman,
+    },
+};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Change {
+    Create,
+    Recreate,
+    Keep,
+    Remove,
+}
+
+/// Change to a resource of the project, with what motivates it
+#[derive(Debug)]
+struct Entry {
+    change: Change,
+    kind: &'static str,
+    name: String,
+    detail: Option<String>,
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let symbol = match self.change {
+            Change::Create => style("  +").green(),
+            Change::Recreate => style("-/+").yellow(),
+            Change::Keep => style("  =").dim(),
+            Change::Remove => style("  -").red(),
+        };
+
+        write!(f, "{symbol} {} {}", self.kind, self.name)?;
+
+        if let Some(detail) = &self.detail {
+            write!(f, " ({detail})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fields whose digest differs between the `config-fields` label of a container and its service
+fn changed_fields(label: &str, desired: &IndexMap<String, String>) -> Vec<String> {
+    let actual = label
+        .split(',')
+        .filter_map(|field| field.split_once(':'))
+        .collect::<IndexMap<_, _>>();
+
+    desired
+        .keys()
+        .map(String::as_str)
+        .chain(actual.keys().copied())
+        .unique()
+        .filter(|field| actual.get(field).copied() != desired.get(*field).map(String::as_str))
+        .map(String::from)
+        .collect()
+}
+
+/// Project resources that are not containers, compared by name
+fn diff<'a>(
+    kind: &'static str,
+    desired: impl Iterator<Item = (&'a String, Option<String>)>,
+    actual: &[String],
+    recreate: Option<&str>,
+    remove_orphans: bool,
+) -> Vec<Entry> {
+    let desired = desired.collect::<IndexMap<_, _>>();
+
+    desired
+        .iter()
+        .map(|(&name, changed)| {
+            let (change, detail) = if !actual.contains(name) {
+                (Change::Create, None)
+            } else if let Some(reason) = recreate {
+                (Change::Recreate, Some(reason.to_string()))
+            } else if let Some(changed) = changed {
+                (Change::Recreate, Some(changed.clone()))
+            } else {
+                (Change::Keep, None)
+            };
+
+            Entry {
+                change,
+                kind,
+                name: name.clone(),
+                detail,
+            }
+        })
+        .chain(
+            actual
+                .iter()
+                .filter(|name| !desired.contains_key(name))
+                .map(|name| Entry {
+                    change: if recreate.is_some() && remove_orphans {
+                        Change::Remove
+                    } else {
+                        Change::Keep
+                    },
+                    kind,
+                    name: name.clone(),
+                    detail: Some(String::from("orphan")),
+                }),
+        )
+        .collect()
+}
+
+/// Prints how `create` would change the project, from its current state and the Compose file
+pub(super) async fn show(
+    podman: &Podman,
+    file: &Compose,
+    args: &Args,
+    recreate: Option<&str>,
+) -> Result<()> {
+    let name = file.name.as_ref().unwrap();
+    let filter = format!("label=io.podman.compose.project={name}");
+    let (containers, networks, volumes, secrets) = try_join!(
+        podman.force_run([
+            "ps",
+            "--all",
+            "--format",
+            "json",
+            "--filter",
+            "label=io.podman.compose.oneoff=false",
+            "--filter",
+            &filter,
+        ]),
+        podman.force_run(["network", "ls", "--format", "json", "--filter", &filter]),
+        podman.force_run(["volume", "ls", "--format", "json", "--filter", &filter]),
+        podman.force_run(["secret", "ls", "--format", "json"]),
+    )?;
+
+    // Services are created along with the ones they depend on
+    let mut services = if args.services.is_empty() {
+        file.services.keys().collect::<IndexSet<_>>()
+    } else {
+        args.services
+            .iter()
+            .filter(|service| file.services.contains_key(*service))
+            .collect()
+    };
+    let mut i = 0;
+
+    while let Some(service) = services.get_index(i) {
+        let dependencies = file.services[*service]
+            .depends_on
+            .keys()
+            .collect::<Vec<_>>();
+
+        services.extend(dependencies);
+        i += 1;
+    }
+
+    let secrets = serde_json::from_str::<Option<Vec<Secret>>>(&secrets)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|secret| {
+            secret
+                .spec
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get("io.podman.compose.project"))
+                == Some(name)
+        })
+        .map(|secret| secret.spec.name)
+        .collect::<Vec<_>>();
+
+    // Secrets whose content changed are replaced, along with the containers using them
+    let replaced = changed_secrets(podman, file, &secrets)
+        .await?
+        .into_iter()
+        .collect::<IndexMap<_, _>>();
+
+    let actual = serde_json::from_str::<Vec<Container>>(&containers)?
+        .into_iter()
+        .filter_map(|mut container| {
+            container
+                .names
+                .pop_front()
+                .map(|name| (name, container.labels))
+        })
+        .collect::<IndexMap<_, _>>();
+    let mut entries = Vec::new();
+
+    for (service_name, service) in &file.services {
+        let fields = field_hashes(service);
+
+        for i in 1..=service
+            .deploy
+            .as_ref()
+            .and_then(|deploy| deploy.replicas)
+            .or(service.scale)
+            .unwrap_or(1)
+        {
+            let container_name = service
+                .container_name
+                .clone()
+                .unwrap_or_else(|| format!("{name}_{service_name}_{i}"));
+            let changed = actual
+                .get(&container_name)
+                .and_then(|labels| labels.as_ref())
+                .and_then(|labels| labels.config_fields.as_ref())
+                .map(|label| changed_fields(label, &fields))
+                .filter(|changed| !changed.is_empty())
+                .map(|changed| changed.join(", "));
+            let secret = service
+                .secrets
+                .iter()
+                .find_map(|secret| replaced.get(&secret.source))
+                .map(|secret| format!("secret {secret} changed"));
+            let (change, detail) = match (
+                services.contains(service_name),
+                actual.contains_key(&container_name),
+            ) {
+                (true, false) => (Change::Create, None),
+                (false, false) => continue,
+                // `down` removes the containers of every service before recreating the selected ones
+                (selected, true) if recreate.is_some() => (
+                    if selected {
+                        Change::Recreate
+                    } else {
+                        Change::Remove
+                    },
+                    Some(changed.unwrap_or_else(|| recreate.unwrap().to_string())),
+                ),
+                (selected, true) if secret.is_some() => (
+                    if selected {
+                        Change::Recreate
+                    } else {
+                        Change::Remove
+                    },
+                    secret,
+                ),
+                (_, true) => (
+                    Change::Keep,
+                    changed.map(|changed| format!("{changed} changed, not recreated")),
+                ),
+            };
+
+            entries.push(Entry {
+                change,
+                kind: "container",
+                name: container_name,
+                detail,
+            });
+        }
+    }
+
+    for (container_name, labels) in &actual {
+        let service = labels.as_ref().and_then(|labels| labels.service.as_ref());
+
+        if !service.is_some_and(|service| file.services.contains_key(service)) {
+            entries.push(Entry {
+                change: if recreate.is_some() && args.remove_orphans {
+                    Change::Remove
+                } else {
+                    Change::Keep
+                },
+                kind: "container",
+                name: container_name.clone(),
+                detail: Some(String::from("orphan")),
+            });
+        }
+    }
+
+    entries.extend(diff(
+        "network",
+        file.networks
+            .values()
+            .filter(|network| !network.external.unwrap_or_default())
+            .filter_map(|network| network.name.as_ref().map(|name| (name, None))),
+        &serde_json::from_str::<Vec<Network>>(&networks)?
+            .into_iter()
+            .map(|network| network.name)
+            .collect::<Vec<_>>(),
+        recreate,
+        args.remove_orphans,
+    ));
+    entries.extend(diff(
+        "volume",
+        file.volumes
+            .values()
+            .filter(|volume| !volume.external.unwrap_or_default())
+            .filter_map(|volume| volume.name.as_ref().map(|name| (name, None))),
+        &serde_json::from_str::<Vec<Volume>>(&volumes)?
+            .into_iter()
+            .map(|volume| volume.name)
+            .collect::<Vec<_>>(),
+        recreate,
+        args.remove_orphans,
+    ));
+
+    entries.extend(diff(
+        "secret",
+        file.secrets
+            .values()
+            .filter(|secret| !secret.external.unwrap_or_default())
+            .filter_map(|secret| {
+                secret.name.as_ref().map(|name| {
+                    (
+                        name,
+                        replaced
+                            .values()
+                            .contains(&name)
+                            .then(|| String::from("content changed")),
+                    )
+                })
+            }),
+        &secrets,
+        recreate,
+        args.remove_orphans,
+    ));
+
+    for entry in &entries {
+        println!("{entry}");
+    }
+
+    let count = |change| {
+        entries
+            .iter()
+            .filter(|entry| entry.change == change)
+            .count()
+    };
+
+    println!(
+        "\nPlan: {} to create, {} to recreate, {} to remove, {} unchanged.",
+        count(Change::Create),
+        count(Change::Recreate),
+        count(Change::Remove),
+        count(Change::Keep)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let desired = [("image", "aaaaaaaa"), ("environment", "bbbbbbbb")]
+            .into_iter()
+            .map(|(field, hash)| (field.to_string(), hash.to_string()))
+            .collect();
+
+        assert_eq!(
+            changed_fields(
+                "image:aaaaaaaa,environment:cccccccc,ports:dddddddd",
+                &desired
+            ),
+            ["environment", "ports"]
+        );
+        assert!(changed_fields("environment:bbbbbbbb,image:aaaaaaaa", &desired).is_empty());
+    }
+
+    #[test]
+    fn orphans() {
+        let name = String::from("proj_default");
+        let actual = [name.clone(), String::from("proj_old")];
+
+        let entries = diff(
+            "network",
+            [(&name, None)].into_iter(),
+            &actual,
+            Some("configuration changed"),
+            true,
+        );
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.change, entry.name.as_str(), entry.detail.as_deref()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    Change::Recreate,
+                    "proj_default",
+                    Some("configuration changed")
+                ),
+                (Change::Remove, "proj_old", Some("orphan")),
+            ]
+        );
+    }
+}
@@ -1,22 +1,23 @@
 use anyhow::Result;
-use futures::{future::try_join3, stream::FuturesUnordered, try_join, TryStreamExt};
+use futures::{future::try_join4, stream::FuturesUnordered, try_join, TryStreamExt};
 use itertools::Itertools;
 
 use crate::{
     commands::{
+        convert::kube,
         rm::{self, remove_containers},
         stop::{self, stop_containers},
     },
-    compose::types::Compose,
+    compose::types::{Backend, Compose},
     config::Config,
     podman::{
-        types::{Container, Network, Volume},
+        types::{Container, Network, Pod, Secret, Volume},
         Podman,
     },
     progress::{Finish, Progress},
 };
 
-/// Stop and remove containers, networks
+/// Stop and remove containers, networks, secrets
 #[derive(clap::Args, Debug)]
 #[command(next_display_order = None)]
 pub(crate) struct Args {
@@ -37,7 +38,11 @@ pub(crate) struct Args {
     pub(crate) rmi: bool,
 }
 
-async fn remove_networks(podman: &Podman, progress: &Progress, networks: &[String]) -> Result<()> {
+pub(crate) async fn remove_networks(
+    podman: &Podman,
+    progress: &Progress,
+    networks: &[String],
+) -> Result<()> {
     networks
         .iter()
         .map(|network| async move {
@@ -54,7 +59,11 @@ async fn remove_networks(podman: &Podman, progress: &Progress, networks: &[Strin
         .map(|_| ())
 }
 
-async fn remove_volumes(podman: &Podman, progress: &Progress, volumes: &[String]) -> Result<()> {
+pub(crate) async fn remove_volumes(
+    podman: &Podman,
+    progress: &Progress,
+    volumes: &[String],
+) -> Result<()> {
     volumes
         .iter()
         .map(|volume| async move {
@@ -71,6 +80,27 @@ async fn remove_volumes(podman: &Podman, progress: &Progress, volumes: &[String]
         .map(|_| ())
 }
 
+pub(crate) async fn remove_secrets(
+    podman: &Podman,
+    progress: &Progress,
+    secrets: &[String],
+) -> Result<()> {
+    secrets
+        .iter()
+        .map(|secret| async move {
+            let spinner = progress.add_spinner(format!("Secret {secret}"), "Removing");
+
+            podman
+                .run(["secret", "rm", secret])
+                .await
+                .finish_with_message(spinner, "Removed")
+        })
+        .collect::<FuturesUnordered<_>>()
+        .try_collect::<Vec<_>>()
+        .await
+        .map(|_| ())
+}
+
 pub(crate) async fn run(
     args: Args,
     podman: &Podman,
@@ -79,7 +109,50 @@ pub(crate) async fn run(
 ) -> Result<()> {
     let name = file.name.as_ref().unwrap();
 
-    let (containers, networks, volumes) = try_join3(
+    let kube = config.backend(file) == Backend::Kube;
+
+    // Volumes and secrets of `podman kube play` are named after the objects they come from
+    let object_name = |name: &String| {
+        if kube {
+            kube::object_name(name)
+        } else {
+            name.clone()
+        }
+    };
+
+    // The pods of `podman kube play` carry the labels of their template, so unlike `podman kube
+    // down` this doesn't need the YAML, whose secrets would have to be read
+    if kube {
+        let output = podman
+            .force_run([
+                "pod",
+                "ps",
+                "--format",
+                "json",
+                "--filter",
+                &format!("label=io.podman.compose.project={name}"),
+            ])
+            .await?;
+        let progress = Progress::new(config);
+
+        serde_json::from_str::<Vec<Pod>>(&output)?
+            .iter()
+            .map(|pod| async {
+                let spinner = progress.add_spinner(format!("Pod {}", pod.name), "Removing");
+
+                podman
+                    .run(["pod", "rm", "--force", &pod.name])
+                    .await
+                    .finish_with_message(spinner, "Removed")
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        progress.finish();
+    }
+
+    let (containers, networks, volumes, secrets) = try_join4(
         podman.force_run(
             [
                 "ps",
@@ -87,7 +160,7 @@ pub(crate) async fn run(
                 "--format",
                 "json",
                 "--filter",
-                &format!("pod={name}"),
+                &format!("label=io.podman.compose.project={name}"),
             ]
             .into_iter()
             .chain(if args.remove_orphans {
@@ -112,6 +185,7 @@ pub(crate) async fn run(
             "--filter",
             &format!("label=io.podman.compose.project={name}"),
         ]),
+        podman.force_run(["secret", "ls", "--format", "json"]),
     )
     .await?;
 
@@ -151,6 +225,7 @@ pub(crate) async fn run(
                     .volumes
                     .values()
                     .filter_map(|volume| volume.name.as_ref())
+                    .map(object_name)
                     .contains(&volume.name)
             {
                 Some(volume.name)
@@ -160,6 +235,34 @@ pub(crate) async fn run(
         })
         .collect::<Vec<_>>();
 
+    let secrets = serde_json::from_str::<Option<Vec<Secret>>>(&secrets)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|secret| {
+            let declared = file
+                .secrets
+                .values()
+                .filter(|secret| !secret.external.unwrap_or_default())
+                .filter_map(|secret| secret.name.as_ref())
+                .map(object_name)
+                .contains(&secret.spec.name);
+
+            if (secret
+                .spec
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get("io.podman.compose.project"))
+                == Some(name)
+                && (args.remove_orphans || declared))
+                || (kube && declared)
+            {
+                Some(secret.spec.name)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
     if !containers.is_empty() {
         let progress = Progress::new(config);
 
@@ -200,11 +303,16 @@ pub(crate) async fn run(
         podman.run(["pod", "rm", "--ignore", name]).await?;
     }
 
-    if !networks.is_empty() || (args.volumes && !volumes.is_empty()) || args.rmi {
+    if !networks.is_empty()
+        || (args.volumes && !volumes.is_empty())
+        || !secrets.is_empty()
+        || args.rmi
+    {
         let progress = Progress::new(config);
 
         try_join!(
             remove_networks(podman, &progress, &networks),
+            remove_secrets(podman, &progress, &secrets),
             async {
                 if args.volumes {
                     remove_volumes(podman, &progress, &volumes).await?;
@@ -1,11 +1,19 @@
-use anyhow::Result;
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
 use futures::TryStreamExt;
-use indexmap::IndexSet;
+use indexmap::IndexMap;
 use itertools::Itertools;
+use serde::Serialize;
 
 use crate::{
     compose::types::Compose,
-    podman::{types::Container, Podman},
+    podman::{
+        types::{Event, Timestamp},
+        Podman,
+    },
+    utils::{parse_key_val, parse_timestamp},
 };
 
 /// Receive real time events from containers
@@ -15,55 +23,216 @@ pub(crate) struct Args {
     services: Vec<String>,
 
     /// Output events as a stream of JSON objects
-    #[arg(long)]
+    #[arg(long, conflicts_with = "format")]
     json: bool,
+
+    /// Format the output
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Show all events created since timestamp (e.g. 2013-01-02T13:23:37Z) or relative (e.g. 42m for 42 minutes)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Stream events until this timestamp (e.g. 2013-01-02T13:23:37Z) or relative (e.g. 42m for 42 minutes)
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Filter output based on conditions provided (type=container|pod|network|volume, event=<action>)
+    #[arg(long, value_parser = parse_key_val::<String, String>)]
+    filter: Vec<(String, String)>,
+}
+
+#[derive(ValueEnum, PartialEq, Clone, Debug)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Serialize, Debug)]
+struct ComposeEvent {
+    time: String,
+    r#type: String,
+    action: String,
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<String>,
+    attributes: IndexMap<String, String>,
+}
+
+impl ComposeEvent {
+    fn new(event: Event, service: Option<String>) -> Self {
+        let time = match (event.time_nano, &event.time) {
+            (Some(nanos), _) => Some(UNIX_EPOCH + Duration::from_nanos(nanos as u64)),
+            (None, Some(Timestamp::Unix(secs))) => {
+                Some(UNIX_EPOCH + Duration::from_secs(*secs as u64))
+            }
+            (None, Some(Timestamp::Rfc3339(time))) => parse_timestamp(time).ok(),
+            (None, None) => None,
+        };
+        let time = time
+            .map(|time| humantime::format_rfc3339_nanos(time).to_string())
+            .or_else(|| event.time.as_ref().map(ToString::to_string))
+            .unwrap_or_default();
+
+        let container = if event.r#type == "container" {
+            Some(event.name.clone())
+        } else {
+            None
+        };
+
+        let mut attributes = IndexMap::new();
+        attributes.insert(String::from("name"), event.name.clone());
+        if let Some(image) = event.image {
+            attributes.insert(String::from("image"), image);
+        }
+        attributes.extend(event.attributes);
+
+        Self {
+            time,
+            r#type: event.r#type,
+            action: event.status,
+            id: event.id.unwrap_or(event.name),
+            service,
+            container,
+            attributes,
+        }
+    }
 }
 
 pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<()> {
-    let output = podman
-        .force_run([
-            "ps",
-            "--all",
-            "--format",
-            "json",
-            "--filter",
-            "label=io.podman.compose.oneoff=false",
-            "--filter",
-            &format!("pod={}", file.name.as_ref().unwrap()),
-        ])
-        .await?;
-    let services = serde_json::from_str::<Vec<Container>>(&output)?
-        .into_iter()
-        .filter_map(|container| {
-            container
-                .labels
-                .and_then(|labels| labels.service)
-                .and_then(|service| {
-                    if args.services.contains(&service)
-                        || (args.services.is_empty() && file.services.keys().contains(&service))
-                    {
-                        Some(format!("label=io.podman.compose.service={service}"))
-                    } else {
-                        None
-                    }
-                })
-        })
-        .collect::<IndexSet<_>>();
+    let name = file.name.as_ref().unwrap();
+    let format = if args.json { Format::Json } else { args.format };
+
+    for (key, value) in &args.filter {
+        match key.as_ref() {
+            "type" => {
+                if !["container", "pod", "network", "volume"].contains(&value.as_ref()) {
+                    bail!("Unsupported event type \"{value}\"");
+                }
+            }
+            "event" => {}
+            _ => bail!("Unsupported filter \"{key}\""),
+        }
+    }
 
-    if !services.is_empty() {
-        let mut output = podman.watch(
-            ["events"]
+    let networks = file
+        .networks
+        .values()
+        .filter_map(|network| network.name.as_ref())
+        .collect::<Vec<_>>();
+    let volumes = file
+        .volumes
+        .values()
+        .filter_map(|volume| volume.name.as_ref())
+        .collect::<Vec<_>>();
+
+    let is_selected = |service: &String| {
+        args.services.contains(service)
+            || (args.services.is_empty() && file.services.keys().contains(service))
+    };
+    let is_filtered = |key: &str, value: &str| {
+        let values = args
+            .filter
+            .iter()
+            .filter(|(k, _)| k == key)
+            .collect::<Vec<_>>();
+
+        values.is_empty() || values.iter().any(|(_, v)| v == value)
+    };
+
+    // Podman requires events to match every filter key, so the project label, which only
+    // containers carry, can narrow down the stream once pods, networks and volumes are left out
+    let types = args
+        .filter
+        .iter()
+        .filter(|(key, _)| key == "type")
+        .map(|(_, value)| value.as_str())
+        .collect::<Vec<_>>();
+    let containers = !args.services.is_empty()
+        || (!types.is_empty() && types.iter().all(|r#type| *r#type == "container"));
+    let filters = args
+        .filter
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .chain(if containers {
+            vec![
+                String::from("type=container"),
+                format!("label=io.podman.compose.project={name}"),
+            ]
+        } else if types.is_empty() {
+            ["container", "pod", "network", "volume"]
                 .into_iter()
-                .chain(if args.json {
-                    vec!["--format", "json"]
-                } else {
-                    vec![]
-                })
-                .chain(services.iter().flat_map(|service| ["--filter", service])),
-        )?;
-
-        while let Some(line) = output.try_next().await? {
-            println!("{line}");
+                .map(|r#type| format!("type={type}"))
+                .collect()
+        } else {
+            vec![]
+        })
+        .unique()
+        .collect::<Vec<_>>();
+
+    let mut output = podman.watch(
+        ["events", "--format", "json"]
+            .into_iter()
+            .chain(filters.iter().flat_map(|filter| ["--filter", filter]))
+            .chain(if let Some(since) = args.since.as_ref() {
+                vec!["--since", since]
+            } else {
+                vec![]
+            })
+            .chain(if let Some(until) = args.until.as_ref() {
+                vec!["--until", until]
+            } else {
+                vec![]
+            }),
+    )?;
+
+    while let Some((_, line)) = output.try_next().await? {
+        let event = serde_json::from_str::<Event>(&line)?;
+
+        if !is_filtered("type", &event.r#type) || !is_filtered("event", &event.status) {
+            continue;
+        }
+
+        let service = match event.r#type.as_ref() {
+            "container" => {
+                let labels = event.labels();
+
+                if event.attributes.get("io.podman.compose.project") != Some(name)
+                    || labels.oneoff.unwrap_or_default()
+                {
+                    continue;
+                }
+
+                match labels.service.filter(is_selected) {
+                    Some(service) => Some(service),
+                    None => continue,
+                }
+            }
+            "pod" if args.services.is_empty() && event.name == *name => None,
+            "network" if args.services.is_empty() && networks.contains(&&event.name) => None,
+            "volume" if args.services.is_empty() && volumes.contains(&&event.name) => None,
+            _ => continue,
+        };
+
+        let event = ComposeEvent::new(event, service);
+
+        match format {
+            Format::Text => println!(
+                "{} {} {} {} ({})",
+                event.time,
+                event.r#type,
+                event.action,
+                event.id,
+                event
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .join(", ")
+            ),
+            Format::Json => println!("{}", serde_json::to_string(&event)?),
         }
     }
 
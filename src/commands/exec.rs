@@ -55,7 +55,10 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
             "--filter",
             "label=io.podman.compose.oneoff=false",
             "--filter",
-            &format!("pod={}", file.name.as_ref().unwrap()),
+            &format!(
+                "label=io.podman.compose.project={}",
+                file.name.as_ref().unwrap()
+            ),
         ])
         .await?;
     let container = serde_json::from_str::<Vec<Container>>(&output)?
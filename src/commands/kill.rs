@@ -63,7 +63,10 @@ pub(crate) async fn run(
             "--filter",
             "status=running",
             "--filter",
-            &format!("pod={}", file.name.as_ref().unwrap()),
+            &format!(
+                "label=io.podman.compose.project={}",
+                file.name.as_ref().unwrap()
+            ),
         ])
         .await?;
     let containers = serde_json::from_str::<Vec<Container>>(&output)?
@@ -1,11 +1,29 @@
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use anyhow::Result;
+use clap::ValueEnum;
 use console::Style;
-use futures::{stream::select_all, TryStreamExt};
+use futures::{
+    future,
+    stream::{self, SelectAll},
+    Stream, StreamExt, TryStreamExt,
+};
 use itertools::Itertools;
+use regex::Regex;
+use serde::Serialize;
+use tokio::select;
 
 use crate::{
-    compose::types::Compose,
-    podman::{types::Container, Podman},
+    compose::types::{Compose, RestartPolicy},
+    podman::{
+        types::{Container, Event, OutputStream},
+        Podman,
+    },
+    utils::parse_timestamp,
 };
 
 /// View output from containers
@@ -41,10 +59,310 @@ pub(crate) struct Args {
     /// Number of lines to show from the end of the logs for each container
     #[arg(long)]
     pub(crate) tail: Option<u32>,
+
+    /// Format the output
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub(crate) format: Format,
+
+    /// Only show lines matching a regular expression
+    #[arg(long)]
+    pub(crate) grep: Option<Regex>,
+
+    /// Only show lines from this output stream
+    #[arg(long, value_enum)]
+    pub(crate) stream: Option<OutputStream>,
+}
+
+#[derive(ValueEnum, PartialEq, Clone, Debug)]
+pub(crate) enum Format {
+    Text,
+    Json,
+}
+
+enum Output {
+    Line(String, OutputStream, String),
+    /// Watcher of a container that ended, with the number it was attached as
+    Ended(String, usize),
+}
+
+type Watcher = Pin<Box<dyn Stream<Item = Result<Output>>>>;
+
+#[derive(Serialize, Debug)]
+struct Record {
+    service: String,
+    container: String,
+    index: Option<usize>,
+    stream: OutputStream,
+    timestamp: Option<String>,
+    message: String,
+    #[serde(skip)]
+    time: SystemTime,
+}
+
+struct Multiplexer<'a> {
+    podman: &'a Podman,
+    args: &'a Args,
+    watchers: SelectAll<Watcher>,
+    /// Containers whose current run is followed, with the number of their watcher
+    watching: HashMap<String, usize>,
+    attached: usize,
+    containers: HashMap<String, (String, Option<usize>)>,
+    styles: HashMap<String, Style>,
+    buffer: Vec<Record>,
+}
+
+impl<'a> Multiplexer<'a> {
+    fn new(podman: &'a Podman, args: &'a Args) -> Self {
+        Self {
+            podman,
+            args,
+            watchers: SelectAll::new(),
+            watching: HashMap::new(),
+            attached: 0,
+            containers: HashMap::new(),
+            styles: HashMap::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn has_timestamps(&self) -> bool {
+        self.args.timestamps || !self.args.follow || self.args.format == Format::Json
+    }
+
+    fn attach(
+        &mut self,
+        container: &str,
+        service: &str,
+        index: Option<usize>,
+        since: Option<&str>,
+        tail: Option<&str>,
+    ) -> Result<()> {
+        let stream = self.podman.watch(
+            ["logs"]
+                .into_iter()
+                .chain(if self.args.follow {
+                    vec!["--follow"]
+                } else {
+                    vec![]
+                })
+                .chain(if let Some(since) = since {
+                    vec!["--since", since]
+                } else {
+                    vec![]
+                })
+                .chain(if let Some(until) = self.args.until.as_ref() {
+                    vec!["--until", until]
+                } else {
+                    vec![]
+                })
+                .chain(if self.has_timestamps() {
+                    vec!["--timestamps"]
+                } else {
+                    vec![]
+                })
+                .chain(if let Some(tail) = tail {
+                    vec!["--tail", tail]
+                } else {
+                    vec![]
+                })
+                .chain([container]),
+        )?;
+
+        if !self.styles.contains_key(container) {
+            let colours = ["cyan", "yellow", "green", "magenta", "blue"];
+            let i = self.styles.len() % (colours.len() * 2);
+
+            let style = if self.args.no_color {
+                Style::new()
+            } else if i < colours.len() {
+                Style::from_dotted_str(colours[i])
+            } else {
+                Style::from_dotted_str(&format!("{}.bright", colours[i - colours.len()]))
+            };
+
+            self.styles.insert(container.to_string(), style);
+        }
+
+        let name = container.to_string();
+        let ended = container.to_string();
+        let number = self.attached;
+
+        self.attached += 1;
+        self.containers
+            .insert(container.to_string(), (service.to_string(), index));
+        self.watching.insert(container.to_string(), number);
+        self.watchers.push(
+            stream
+                .map_ok(move |(stream, line)| Output::Line(name.clone(), stream, line))
+                .chain(stream::once(future::ready(Ok(Output::Ended(
+                    ended, number,
+                )))))
+                .boxed_local(),
+        );
+
+        Ok(())
+    }
+
+    fn print(&self, record: &Record) -> Result<()> {
+        if self.args.format == Format::Json {
+            println!("{}", serde_json::to_string(record)?);
+
+            return Ok(());
+        }
+
+        let message = match (&record.timestamp, self.args.timestamps) {
+            (Some(timestamp), true) => format!("{timestamp} {}", record.message),
+            _ => record.message.clone(),
+        };
+        let message = if record.stream == OutputStream::Stderr && !self.args.no_color {
+            Style::new().red().apply_to(message).to_string()
+        } else {
+            message
+        };
+
+        if self.args.no_log_prefix {
+            println!("{message}");
+        } else {
+            let width = self
+                .styles
+                .keys()
+                .map(String::len)
+                .max()
+                .unwrap_or_default();
+
+            println!(
+                "{} {message}",
+                self.styles[&record.container].apply_to(format!("{:width$}  |", record.container))
+            );
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_by_key(|record| record.time);
+
+        for record in buffer {
+            self.print(&record)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_exit(&self, container: &str, exit_code: i32) {
+        if self.args.format == Format::Json {
+            return;
+        }
+
+        let message = format!("{container} exited with code {exit_code}");
+
+        if let Some(style) = self.styles.get(container) {
+            println!("{}", style.apply_to(message));
+        } else {
+            println!("{message}");
+        }
+    }
+
+    fn handle(&mut self, output: Output) -> Result<()> {
+        match output {
+            Output::Line(container, stream, line) => {
+                let (time, timestamp, message) = line
+                    .split_once(' ')
+                    .filter(|_| self.has_timestamps())
+                    .and_then(|(timestamp, message)| {
+                        parse_timestamp(timestamp)
+                            .ok()
+                            .map(|time| (time, Some(timestamp.to_string()), message))
+                    })
+                    .unwrap_or_else(|| {
+                        // Lines without a timestamp are kept right after the previous line of the container
+                        let time = self
+                            .buffer
+                            .iter()
+                            .rev()
+                            .find(|record| record.container == container)
+                            .map_or(UNIX_EPOCH, |record| record.time);
+
+                        (time, None, line.as_ref())
+                    });
+
+                if self.args.stream.is_some_and(|filter| filter != stream)
+                    || self
+                        .args
+                        .grep
+                        .as_ref()
+                        .is_some_and(|grep| !grep.is_match(message))
+                {
+                    return Ok(());
+                }
+
+                let (service, index) = self.containers[&container].clone();
+                let record = Record {
+                    service,
+                    container,
+                    index,
+                    stream,
+                    timestamp,
+                    message: message.to_string(),
+                    time,
+                };
+
+                if self.args.follow {
+                    self.print(&record)?;
+                } else {
+                    self.buffer.push(record);
+                }
+            }
+            // The container may have been attached again since, once it restarted
+            Output::Ended(container, number) => {
+                if self.watching.get(&container) == Some(&number) {
+                    self.watching.remove(&container);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_restarting(file: &Compose, service: &str, exit_code: i32) -> bool {
+    match file
+        .services
+        .get(service)
+        .and_then(|service| service.restart.as_ref())
+    {
+        Some(RestartPolicy::Always | RestartPolicy::UnlessStopped) => true,
+        Some(RestartPolicy::OnFailure) => exit_code != 0,
+        Some(RestartPolicy::No) | None => false,
+    }
 }
 
 pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<()> {
+    let name = file.name.as_ref().unwrap();
     let tail = args.tail.map(|tail| tail.to_string());
+    let is_selected = |service: &String| {
+        args.services.contains(service)
+            || (args.services.is_empty() && file.services.keys().contains(service))
+    };
+
+    let mut events = if args.follow {
+        Some(
+            podman
+                .watch([
+                    "events",
+                    "--format",
+                    "json",
+                    "--filter",
+                    "type=container",
+                    "--filter",
+                    &format!("label=io.podman.compose.project={name}"),
+                ])?
+                .fuse(),
+        )
+    } else {
+        None
+    };
 
     let output = podman
         .force_run([
@@ -55,98 +373,124 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
             "--filter",
             "label=io.podman.compose.oneoff=false",
             "--filter",
-            &format!("pod={}", file.name.as_ref().unwrap()),
+            &format!("label=io.podman.compose.project={name}"),
         ])
         .await?;
     let containers = serde_json::from_str::<Vec<Container>>(&output)?
         .into_iter()
         .filter_map(|mut container| {
-            container
-                .labels
-                .and_then(|labels| labels.service)
-                .and_then(|service| {
-                    if args.services.contains(&service)
-                        || (args.services.is_empty() && file.services.keys().contains(&service))
-                    {
-                        container.names.pop_front()
+            container.labels.and_then(|labels| {
+                labels.service.and_then(|service| {
+                    if is_selected(&service) {
+                        container.names.pop_front().map(|name| {
+                            (
+                                name,
+                                service,
+                                labels.container_number,
+                                container.state.as_deref() == Some("running"),
+                            )
+                        })
                     } else {
                         None
                     }
                 })
+            })
         })
         .collect::<Vec<_>>();
 
-    if !containers.is_empty() {
-        let colours = ["cyan", "yellow", "green", "magenta", "blue"];
-        let width = containers.iter().map(String::len).max().unwrap_or_default();
+    let mut multiplexer = Multiplexer::new(podman, &args);
+    let mut running = HashSet::new();
 
-        let mut output = select_all(
-            containers
-                .into_iter()
-                .enumerate()
-                .map(|(i, container)| {
-                    podman
-                        .watch(
-                            ["logs"]
-                                .into_iter()
-                                .chain(if args.follow {
-                                    vec!["--follow"]
-                                } else {
-                                    vec![]
-                                })
-                                .chain(if let Some(since) = args.since.as_ref() {
-                                    vec!["--since", since]
-                                } else {
-                                    vec![]
-                                })
-                                .chain(if let Some(until) = args.until.as_ref() {
-                                    vec!["--until", until]
-                                } else {
-                                    vec![]
-                                })
-                                .chain(if args.timestamps {
-                                    vec!["--timestamps"]
-                                } else {
-                                    vec![]
-                                })
-                                .chain(if let Some(tail) = tail.as_ref() {
-                                    vec!["--tail", tail]
-                                } else {
-                                    vec![]
-                                })
-                                .chain([container.as_ref()]),
-                        )
-                        .map(|stream| {
-                            let i = i % (colours.len() * 2);
-
-                            let style = if args.no_color {
-                                Style::new()
-                            } else if i < colours.len() {
-                                Style::from_dotted_str(colours[i])
-                            } else {
-                                Style::from_dotted_str(&format!(
-                                    "{}.bright",
-                                    colours[i - colours.len()]
-                                ))
-                            };
-
-                            stream.map_ok(move |line| {
-                                if args.no_log_prefix {
-                                    line
-                                } else {
-                                    format!(
-                                        "{} {line}",
-                                        style.apply_to(format!("{container:width$}  |"))
+    for (container, service, index, is_running) in containers {
+        multiplexer.attach(
+            &container,
+            &service,
+            index,
+            args.since.as_deref(),
+            tail.as_deref(),
+        )?;
+
+        // The logs of a stopped container end with its last run, and a new run needs a watcher
+        if is_running {
+            running.insert(container);
+        } else {
+            multiplexer.watching.remove(&container);
+        }
+    }
+
+    loop {
+        if running.is_empty() && multiplexer.watchers.is_empty() {
+            break;
+        }
+
+        let Some(events) = events.as_mut().filter(|_| !running.is_empty()) else {
+            // Nothing else will be started, so only drain the remaining logs
+            while let Some(output) = multiplexer.watchers.try_next().await? {
+                multiplexer.handle(output)?;
+            }
+
+            multiplexer.flush()?;
+
+            break;
+        };
+
+        select! {
+            Some(output) = multiplexer.watchers.next(), if !multiplexer.watchers.is_empty() => {
+                multiplexer.handle(output?)?;
+            }
+            event = events.next() => {
+                let Some(event) = event else {
+                    running.clear();
+                    continue;
+                };
+                let event = serde_json::from_str::<Event>(&event?.1)?;
+                let labels = event.labels();
+                let Some(service) = labels.service.filter(|service| {
+                    !labels.oneoff.unwrap_or_default() && is_selected(service)
+                }) else {
+                    continue;
+                };
+
+                match event.status.as_ref() {
+                    "start" => {
+                        running.insert(event.name.clone());
+
+                        // `time` only has a precision of seconds, which would repeat or lose lines
+                        if !multiplexer.watching.contains_key(&event.name) {
+                            let since = event
+                                .time_nano
+                                .map(|time| {
+                                    humantime::format_rfc3339_nanos(
+                                        UNIX_EPOCH + Duration::from_nanos(time as u64),
                                     )
-                                }
-                            })
-                        })
-                })
-                .collect::<Result<Vec<_>>>()?,
-        );
+                                    .to_string()
+                                })
+                                .or_else(|| event.time.map(|time| time.to_string()));
+
+                            multiplexer.attach(
+                                &event.name,
+                                &service,
+                                labels.container_number,
+                                since.as_deref(),
+                                None,
+                            )?;
+                        }
+                    }
+                    "died" => {
+                        let exit_code = event.container_exit_code.unwrap_or_default();
 
-        while let Some(line) = output.try_next().await? {
-            println!("{line}");
+                        multiplexer.print_exit(&event.name, exit_code);
+
+                        // Its watcher may not have ended yet, while a restart needs a new one
+                        multiplexer.watching.remove(&event.name);
+
+                        if !is_restarting(file, &service, exit_code) {
+                            running.remove(&event.name);
+                        }
+                    }
+                    _ => {}
+                }
+            }
         }
     }
 
@@ -1,82 +1,170 @@
-use anyhow::Result;
-use clap::ValueEnum;
+use anyhow::{bail, Result};
+use futures::future::try_join;
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use serde::Serialize;
 
-use crate::podman::Podman;
+use crate::{
+    format::Format,
+    podman::{
+        types::{Container, Pod, PodLabels},
+        Podman,
+    },
+    utils::parse_key_val,
+};
 
 /// List running Compose projects
 #[derive(clap::Args, Debug)]
 #[command(next_display_order = None)]
 pub(crate) struct Args {
-    /// Format the output
-    #[arg(long, value_enum, default_value_t = Format::Table)]
+    /// Format the output: table, json, or a Go template (e.g. '{{.Name}} {{.ConfigFiles}}')
+    #[arg(long, default_value = "table")]
     format: Format,
 
     /// Only display IDs
     #[arg(short, long)]
     quiet: bool,
 
-    /// Filter output based on conditions provided
-    #[arg(long)]
-    filter: Vec<String>,
+    /// Filter output based on conditions provided (name=<name>, status=<status>)
+    #[arg(long, value_parser = parse_key_val::<String, String>)]
+    filter: Vec<(String, String)>,
 
     /// Show all stopped Compose projects
     #[arg(short, long)]
     all: bool,
 }
 
-#[derive(ValueEnum, PartialEq, Clone, Debug)]
-enum Format {
-    Table,
-    Json,
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Row {
+    name: String,
+    status: String,
+    config_files: String,
+    working_dir: String,
 }
 
 pub(crate) async fn run(args: Args, podman: &Podman) -> Result<()> {
+    for (key, _) in &args.filter {
+        if key != "name" && key != "status" {
+            bail!("Unsupported filter \"{key}\"");
+        }
+    }
+
+    let (pods, containers) = try_join(
+        podman.force_run([
+            "pod",
+            "ps",
+            "--format",
+            "json",
+            "--filter",
+            "label=io.podman.compose.project",
+        ]),
+        podman.force_run([
+            "ps",
+            "--all",
+            "--format",
+            "json",
+            "--filter",
+            "label=io.podman.compose.project",
+        ]),
+    )
+    .await?;
+    // Projects run through `podman kube play` span a pod per service
+    let mut projects = serde_json::from_str::<Vec<Pod>>(&pods)?.into_iter().fold(
+        IndexMap::<_, (IndexMap<_, usize>, PodLabels)>::new(),
+        |mut projects, pod| {
+            let labels = pod.labels.unwrap_or_default();
+            let (statuses, project_labels) = projects
+                .entry(labels.project.clone().unwrap_or(pod.name))
+                .or_default();
+
+            for container in pod
+                .containers
+                .iter()
+                .filter(|container| container.id != pod.infra_id)
+            {
+                *statuses
+                    .entry(container.status.to_ascii_lowercase())
+                    .or_default() += 1;
+            }
+
+            if labels.config_files.is_some() {
+                *project_labels = labels;
+            }
+
+            projects
+        },
+    );
+    let pod_projects = projects.keys().cloned().collect::<IndexSet<_>>();
+
+    // Projects without a pod are only known from the labels of their containers
+    for container in serde_json::from_str::<Vec<Container>>(&containers)? {
+        let Some(labels) = container.labels else {
+            continue;
+        };
+        let Some(project) = labels
+            .project
+            .clone()
+            .filter(|project| !pod_projects.contains(project))
+        else {
+            continue;
+        };
+        let (statuses, project_labels) = projects.entry(project).or_default();
+
+        *statuses
+            .entry(container.state.unwrap_or_default().to_ascii_lowercase())
+            .or_default() += 1;
+
+        if labels.config_files.is_some() {
+            *project_labels = labels.into();
+        }
+    }
+
+    let rows = projects
+        .into_iter()
+        .filter_map(|(name, (mut statuses, labels))| {
+            statuses.sort_keys();
+
+            let is_matched = |key: &str, is_match: &dyn Fn(&str) -> bool| {
+                args.filter
+                    .iter()
+                    .filter(|(k, _)| k == key)
+                    .all(|(_, value)| is_match(value))
+            };
+
+            if (!args.all && !statuses.contains_key("running"))
+                || !is_matched("name", &|filter| name.contains(filter))
+                || !is_matched("status", &|status| statuses.contains_key(status))
+            {
+                return None;
+            }
+
+            Some(Row {
+                name,
+                status: statuses
+                    .iter()
+                    .map(|(status, count)| format!("{status}({count})"))
+                    .join(", "),
+                config_files: labels.config_files.unwrap_or_default(),
+                working_dir: labels.working_dir.unwrap_or_default(),
+            })
+        })
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect::<Vec<_>>();
+
     if args.quiet {
-        print!(
-            "{}",
-            podman
-                .run(
-                    [
-                        "pod",
-                        "ps",
-                        "--quiet",
-                        "--filter",
-                        "label=io.podman.compose.project"
-                    ]
-                    .into_iter()
-                    .chain(args.filter.iter().flat_map(|filter| ["--filter", filter]))
-                    .chain(if args.all {
-                        vec![]
-                    } else {
-                        vec!["--filter", "status=running"]
-                    })
-                )
-                .await?
-        );
+        for row in rows {
+            println!("{}", row.name);
+        }
     } else {
-        print!(
-            "{}",
-            podman
-                .run(
-                    ["pod", "ps", "--filter", "label=io.podman.compose.project"]
-                        .into_iter()
-                        .chain([
-                            "--format",
-                            match args.format {
-                                Format::Table =>
-                                    "table {{.Name}} {{.Status}} {{.Created}} {{.NumberOfContainers}}",
-                                Format::Json => "json",
-                            }
-                        ])
-                        .chain(args.filter.iter().flat_map(|filter| ["--filter", filter]))
-                        .chain(if args.all {
-                            vec![]
-                        } else {
-                            vec!["--filter", "status=running"]
-                        })
-                )
-                .await?
-        );
+        args.format.print(
+            &[
+                ("NAME", "Name"),
+                ("STATUS", "Status"),
+                ("CONFIG FILES", "ConfigFiles"),
+            ],
+            &rows,
+        )?;
     }
 
     Ok(())
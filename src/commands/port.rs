@@ -45,7 +45,10 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
             "--filter",
             "label=io.podman.compose.oneoff=false",
             "--filter",
-            &format!("pod={}", file.name.as_ref().unwrap()),
+            &format!(
+                "label=io.podman.compose.project={}",
+                file.name.as_ref().unwrap()
+            ),
             "--filter",
             &format!("label=io.podman.compose.service={}", args.service),
         ])
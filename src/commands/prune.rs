@@ -0,0 +1,387 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use futures::{stream::FuturesUnordered, try_join, TryStreamExt};
+use indexmap::IndexMap;
+use inquire::Confirm;
+
+use crate::{
+    commands::down::{remove_networks, remove_secrets, remove_volumes},
+    compose::types::Compose,
+    config::Config,
+    podman::{
+        types::{Container, Image, Network, Pod, Secret, Volume},
+        Podman,
+    },
+    progress::{Finish, Progress},
+};
+
+/// Remove stopped one-off containers, orphaned project resources and dangling images
+#[derive(clap::Args, Debug)]
+#[command(next_display_order = None)]
+pub(crate) struct Args {
+    /// Don't ask to confirm removal
+    #[arg(short, long)]
+    force: bool,
+
+    /// Prune resources of all Compose projects, including those whose containers and pods no
+    /// longer exist
+    #[arg(long)]
+    all_projects: bool,
+
+    /// With `--all-projects`, also remove the volumes of projects that no longer exist
+    #[arg(short, long)]
+    volumes: bool,
+}
+
+const PROJECT_LABEL: &str = "io.podman.compose.project";
+
+async fn remove_containers(
+    podman: &Podman,
+    progress: &Progress,
+    containers: &[String],
+) -> Result<()> {
+    containers
+        .iter()
+        .map(|container| async move {
+            let spinner = progress.add_spinner(format!("Container {container}"), "Removing");
+
+            podman
+                .run(["rm", "--ignore", container])
+                .await
+                .finish_with_message(spinner, "Removed")
+        })
+        .collect::<FuturesUnordered<_>>()
+        .try_collect::<Vec<_>>()
+        .await
+        .map(|_| ())
+}
+
+async fn remove_images(podman: &Podman, progress: &Progress, images: &[String]) -> Result<()> {
+    images
+        .iter()
+        .map(|image| async move {
+            let spinner = progress.add_spinner(format!("Image {image}"), "Removing");
+
+            podman
+                .run(["rmi", "--ignore", image])
+                .await
+                .finish_with_message(spinner, "Removed")
+        })
+        .collect::<FuturesUnordered<_>>()
+        .try_collect::<Vec<_>>()
+        .await
+        .map(|_| ())
+}
+
+pub(crate) async fn run(
+    args: Args,
+    podman: &Podman,
+    file: &Compose,
+    config: &Config,
+) -> Result<()> {
+    let name = file.name.as_ref().unwrap();
+    let projects_filter = format!("label={PROJECT_LABEL}");
+    let label_filter = if args.all_projects {
+        projects_filter.clone()
+    } else {
+        format!("{projects_filter}={name}")
+    };
+
+    let (containers, networks, volumes, secrets, images, pods) = try_join!(
+        podman.force_run(["ps", "--all", "--format", "json", "--filter", &label_filter]),
+        podman.force_run([
+            "network",
+            "ls",
+            "--format",
+            "json",
+            "--filter",
+            &label_filter
+        ]),
+        podman.force_run([
+            "volume",
+            "ls",
+            "--format",
+            "json",
+            "--filter",
+            &label_filter
+        ]),
+        podman.force_run(["secret", "ls", "--format", "json"]),
+        podman.force_run([
+            "image",
+            "ls",
+            "--format",
+            "json",
+            "--filter",
+            "dangling=true",
+            "--filter",
+            &label_filter,
+        ]),
+        podman.force_run([
+            "pod",
+            "ps",
+            "--format",
+            "json",
+            "--filter",
+            &projects_filter,
+        ]),
+    )?;
+
+    let containers = serde_json::from_str::<Vec<Container>>(&containers)?;
+    let volumes = serde_json::from_str::<Vec<Volume>>(&volumes)?;
+
+    // A project is gone once none of its containers, pods, whatever their names, and volumes are
+    // left, its volumes only being given up with `--volumes`. Its networks and secrets are what it
+    // leaves behind, so they don't count
+    let projects = containers
+        .iter()
+        .filter_map(|container| container.labels.as_ref()?.project.clone())
+        .chain(
+            serde_json::from_str::<Vec<Pod>>(&pods)?
+                .into_iter()
+                .filter_map(|pod| pod.labels?.project),
+        )
+        .chain(
+            volumes
+                .iter()
+                .filter(|_| !args.volumes)
+                .filter_map(|volume| volume.labels.as_ref()?.get(PROJECT_LABEL).cloned()),
+        )
+        .collect::<HashSet<_>>();
+
+    // A resource is orphaned if the Compose file no longer references it, or if its project is gone
+    let is_orphaned =
+        |labels: Option<&IndexMap<String, String>>, resource: &String, references: Vec<&String>| {
+            match labels.and_then(|labels| labels.get(PROJECT_LABEL)) {
+                Some(project) if project == name => !references.contains(&resource),
+                Some(project) => args.all_projects && !projects.contains(project),
+                None => false,
+            }
+        };
+
+    let containers = containers
+        .into_iter()
+        .filter(|container| {
+            container
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.oneoff)
+                .unwrap_or_default()
+                && !matches!(
+                    container.state.as_deref(),
+                    Some("running" | "paused" | "stopping")
+                )
+        })
+        .filter_map(|mut container| container.names.pop_front())
+        .collect::<Vec<_>>();
+    let networks = serde_json::from_str::<Vec<Network>>(&networks)?
+        .into_iter()
+        .filter(|network| {
+            is_orphaned(
+                network.labels.as_ref(),
+                &network.name,
+                file.networks
+                    .values()
+                    .filter_map(|network| network.name.as_ref())
+                    .collect(),
+            )
+        })
+        .map(|network| network.name)
+        .collect::<Vec<_>>();
+    let volumes = volumes
+        .into_iter()
+        .filter(|volume| {
+            is_orphaned(
+                volume.labels.as_ref(),
+                &volume.name,
+                file.volumes
+                    .values()
+                    .filter_map(|volume| volume.name.as_ref())
+                    .collect(),
+            )
+        })
+        .map(|volume| volume.name)
+        .collect::<Vec<_>>();
+    let secrets = serde_json::from_str::<Option<Vec<Secret>>>(&secrets)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|secret| {
+            // After a rehydration from labels, the secrets of the Compose file are unknown
+            (!config.files.is_empty()
+                || secret
+                    .spec
+                    .labels
+                    .as_ref()
+                    .and_then(|labels| labels.get(PROJECT_LABEL))
+                    != Some(name))
+                && is_orphaned(
+                    secret.spec.labels.as_ref(),
+                    &secret.spec.name,
+                    file.secrets
+                        .values()
+                        .filter_map(|secret| secret.name.as_ref())
+                        .collect(),
+                )
+        })
+        .map(|secret| secret.spec.name)
+        .collect::<Vec<_>>();
+    let images = serde_json::from_str::<Vec<Image>>(&images)?
+        .into_iter()
+        .filter(|image| is_orphaned(image.labels.as_ref(), &image.id, Vec::new()))
+        .map(|image| image.id)
+        .collect::<Vec<_>>();
+
+    let resources = [
+        ("container", &containers),
+        ("network", &networks),
+        ("volume", &volumes),
+        ("secret", &secrets),
+        ("image", &images),
+    ]
+    .into_iter()
+    .flat_map(|(kind, resources)| {
+        resources
+            .iter()
+            .map(move |resource| format!("{kind} {resource}"))
+    })
+    .collect::<Vec<_>>();
+
+    if !resources.is_empty()
+        && (args.force
+            || config.dry_run
+            || Confirm::new(&format!("Going to remove {}", resources.join(", ")))
+                .with_default(false)
+                .prompt()?)
+    {
+        let progress = Progress::new(config);
+
+        // Containers go first, as they may still use the images
+        remove_containers(podman, &progress, &containers).await?;
+        try_join!(
+            remove_networks(podman, &progress, &networks),
+            remove_volumes(podman, &progress, &volumes),
+            remove_secrets(podman, &progress, &secrets),
+            remove_images(podman, &progress, &images),
+        )?;
+
+        progress.finish();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use itertools::Itertools;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{podman::fake::Fake, progress::ProgressMode};
+
+    /// Prunes all projects from project "proj", returning the removals
+    fn prune(volumes: bool, fake: Fake) -> Vec<String> {
+        let file = serde_yaml::from_str::<Compose>("name: proj\nservices: {}").unwrap();
+        let config = Config {
+            files: vec![PathBuf::from("compose.yaml")],
+            progress: ProgressMode::Quiet,
+            ..Config::default()
+        };
+        let fake = fake
+            .script(&["ps"], Ok("[]"))
+            .script(&["pod", "ps"], Ok("[]"))
+            .script(&["network", "ls"], Ok("[]"))
+            .script(&["volume", "ls"], Ok("[]"))
+            .script(&["secret", "ls"], Ok("[]"))
+            .script(&["image", "ls"], Ok("[]"));
+        let invocations = fake.invocations();
+        let podman = Podman::with_executor(Box::new(fake), &config);
+
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(run(
+                Args {
+                    force: true,
+                    all_projects: true,
+                    volumes,
+                },
+                &podman,
+                &file,
+                &config,
+            ))
+            .unwrap();
+
+        invocations
+            .take()
+            .into_iter()
+            .map(|(_, invocation)| invocation)
+            .filter(|invocation| invocation.contains(" rm ") || invocation.contains(" rmi "))
+            .sorted()
+            .collect()
+    }
+
+    fn labels(project: &str) -> Value {
+        json!({ PROJECT_LABEL: project })
+    }
+
+    #[test]
+    fn pod_less_project() {
+        let networks = json!([
+            { "name": "solo_default", "labels": labels("solo") },
+            { "name": "gone_default", "labels": labels("gone") },
+        ]);
+        let volumes = json!([{ "Name": "solo_data", "Labels": labels("solo") }]);
+        let containers = json!([{
+            "Id": "web-id",
+            "Names": ["solo_web_1"],
+            "Labels": { PROJECT_LABEL: "solo", "io.podman.compose.oneoff": "false" },
+            "State": "exited",
+        }]);
+        let images = json!([{ "Id": "dangling-id", "Labels": labels("gone") }]);
+
+        // Containers outside of a pod keep their project alive
+        assert_eq!(
+            prune(
+                true,
+                Fake::default()
+                    .script(&["network", "ls"], Ok(&networks.to_string()))
+                    .script(&["volume", "ls"], Ok(&volumes.to_string()))
+                    .script(&["ps"], Ok(&containers.to_string()))
+                    .script(&["image", "ls"], Ok(&images.to_string()))
+            ),
+            [
+                "podman network rm gone_default",
+                "podman rmi --ignore dangling-id"
+            ]
+        );
+    }
+
+    #[test]
+    fn kube_project() {
+        let networks = json!([{ "name": "kube_default", "labels": labels("kube") }]);
+        let pods = json!([{ "Name": "kube-web-pod", "Labels": labels("kube") }]);
+
+        // Pods of `podman kube play` are named after their deployment rather than the project
+        assert_eq!(
+            prune(
+                true,
+                Fake::default()
+                    .script(&["network", "ls"], Ok(&networks.to_string()))
+                    .script(&["pod", "ps"], Ok(&pods.to_string()))
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn volumes() {
+        let volumes = json!([{ "Name": "kept_data", "Labels": labels("kept") }]);
+        let fake = || Fake::default().script(&["volume", "ls"], Ok(&volumes.to_string()));
+
+        // The volumes a project was taken down without are only removed when asked to
+        assert_eq!(prune(false, fake()), Vec::<String>::new());
+        assert_eq!(prune(true, fake()), ["podman volume rm kept_data"]);
+    }
+}
@@ -1,12 +1,18 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::Result;
 use clap::ValueEnum;
 use itertools::Itertools;
+use serde::Serialize;
 
 use crate::{
     compose::types::Compose,
+    format::Format,
     podman::{types::Container, Podman},
+    utils::human_duration,
 };
 
 /// List containers
@@ -15,8 +21,8 @@ use crate::{
 pub(crate) struct Args {
     services: Vec<String>,
 
-    /// Format the output
-    #[arg(long, value_enum, default_value_t = Format::Table)]
+    /// Format the output: table, json, or a Go template (e.g. '{{.Service}} {{.Health}}')
+    #[arg(long, default_value = "table")]
     format: Format,
 
     /// Filter services by a property
@@ -40,10 +46,34 @@ pub(crate) struct Args {
     all: bool,
 }
 
-#[derive(ValueEnum, PartialEq, Clone, Debug)]
-enum Format {
-    Table,
-    Json,
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Row {
+    #[serde(rename = "ID")]
+    id: String,
+    name: String,
+    image: String,
+    command: String,
+    project: String,
+    service: String,
+    created: i64,
+    running_for: String,
+    state: String,
+    status: String,
+    health: String,
+    exit_code: i32,
+    ports: String,
+    publishers: Vec<Publisher>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Publisher {
+    #[serde(rename = "URL")]
+    url: String,
+    target_port: u16,
+    published_port: u16,
+    protocol: String,
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -61,6 +91,13 @@ impl Display for Status {
     }
 }
 
+/// Health of a container from its status, which only holds it when it has a healthcheck
+fn health(status: Option<String>) -> String {
+    status
+        .filter(|status| ["healthy", "unhealthy", "starting"].contains(&status.as_str()))
+        .unwrap_or_default()
+}
+
 pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<()> {
     let name = file.name.as_ref().unwrap();
 
@@ -72,14 +109,20 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
         .collect::<Vec<_>>();
     let output = podman
         .force_run(
-            ["ps", "--format", "json", "--filter", &format!("pod={name}")]
-                .into_iter()
-                .chain(filters.iter().flat_map(|filter| ["--filter", filter]))
-                .chain(if args.all {
-                    vec!["--all"]
-                } else {
-                    vec!["--filter", "label=io.podman.compose.oneoff=false"]
-                }),
+            [
+                "ps",
+                "--format",
+                "json",
+                "--filter",
+                &format!("label=io.podman.compose.project={name}"),
+            ]
+            .into_iter()
+            .chain(filters.iter().flat_map(|filter| ["--filter", filter]))
+            .chain(if args.all {
+                vec!["--all"]
+            } else {
+                vec!["--filter", "label=io.podman.compose.oneoff=false"]
+            }),
         )
         .await?;
     let containers = serde_json::from_str::<Vec<Container>>(&output)?
@@ -109,34 +152,121 @@ pub(crate) async fn run(args: Args, podman: &Podman, file: &Compose) -> Result<(
         for service in containers.into_keys() {
             println!("{service}");
         }
-    } else if !containers.is_empty() {
-        let filters = containers
-            .into_values()
-            .flatten()
-            .filter_map(|container| container.names.front().map(|name| format!("name=^{name}$")))
-            .chain(filters)
+    } else {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let since = |time: i64| human_duration(Duration::from_secs((now - time).max(0) as u64));
+
+        let rows = containers
+            .into_iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .flat_map(|(service, containers)| {
+                containers
+                    .into_iter()
+                    .sorted_by(|a, b| a.names.front().cmp(&b.names.front()))
+                    .map(move |container| (service.clone(), container))
+            })
+            .map(|(service, mut container)| {
+                let health = health(container.status.take());
+                let state = container.state.take().unwrap_or_default();
+                let status = match state.as_ref() {
+                    "running" | "paused" => format!(
+                        "Up {}{}",
+                        since(container.started_at),
+                        match (state.as_ref(), health.as_ref()) {
+                            ("paused", _) => String::from(" (Paused)"),
+                            (_, "") => String::new(),
+                            (_, health) => format!(" ({health})"),
+                        }
+                    ),
+                    "exited" | "stopped" => format!(
+                        "Exited ({}) {} ago",
+                        container.exit_code,
+                        since(container.exited_at)
+                    ),
+                    "created" | "configured" | "initialized" => String::from("Created"),
+                    state => state.to_string(),
+                };
+                let publishers = container
+                    .ports
+                    .take()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .flat_map(|port| {
+                        (0..port.range).map(move |i| Publisher {
+                            url: if port.host_ip.is_empty() {
+                                String::from("0.0.0.0")
+                            } else {
+                                port.host_ip.clone()
+                            },
+                            target_port: port.container_port + i,
+                            published_port: port.host_port + i,
+                            protocol: port.protocol.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                Row {
+                    id: container.id,
+                    name: container.names.pop_front().unwrap_or_default(),
+                    image: container.image,
+                    command: format!("\"{}\"", container.command.unwrap_or_default().join(" ")),
+                    project: name.clone(),
+                    service,
+                    created: container.created,
+                    running_for: format!("{} ago", since(container.created)),
+                    state,
+                    status,
+                    health,
+                    exit_code: container.exit_code,
+                    ports: publishers
+                        .iter()
+                        .map(|publisher| {
+                            format!(
+                                "{}:{}->{}/{}",
+                                publisher.url,
+                                publisher.published_port,
+                                publisher.target_port,
+                                publisher.protocol
+                            )
+                        })
+                        .join(", "),
+                    publishers,
+                }
+            })
             .collect::<Vec<_>>();
 
-        print!(
-            "{}",
-            podman
-                .run(
-                    ["ps", "--filter", &format!("pod={name}")]
-                        .into_iter()
-                        .chain([
-                            "--format",
-                            match args.format {
-                                Format::Table =>
-                                    "table {{.Names}} {{.ID}} {{.Image}} {{.Command}} {{.CreatedHuman}} {{.Status}} {{.Ports}}",
-                                Format::Json => "json",
-                            }
-                        ])
-                        .chain(filters.iter().flat_map(|filter| ["--filter", filter]))
-                        .chain(if args.all { vec!["--all"] } else { vec![] })
-                )
-                .await?
-        );
+        args.format.print(
+            &[
+                ("NAME", "Name"),
+                ("SERVICE", "Service"),
+                ("IMAGE", "Image"),
+                ("COMMAND", "Command"),
+                ("CREATED", "RunningFor"),
+                ("STATUS", "Status"),
+                ("PORTS", "Ports"),
+            ],
+            &rows,
+        )?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn health_from_status() {
+        assert_eq!(health(Some(String::from("healthy"))), "healthy");
+        assert_eq!(health(Some(String::from("starting"))), "starting");
+        assert_eq!(health(Some(String::from("Up 5 minutes"))), "");
+        assert_eq!(health(Some(String::new())), "");
+        assert_eq!(health(None), "");
+    }
+}
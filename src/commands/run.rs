@@ -1,18 +1,16 @@
 use std::{iter::repeat_with, path::PathBuf};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use atty::Stream;
-use clap::crate_version;
 use fastrand::Rng;
 
 use crate::{
     commands::{create, start},
     compose::types::{
-        parse_port, parse_service_volume, Compose, FileReference, Port, Service, ServiceVolume,
-        ServiceVolumeType,
+        parse_port, parse_service_volume, Backend, Compose, Port, Service, ServiceVolume,
     },
     config::Config,
-    podman::Podman,
+    podman::{spec::ContainerSpec, Podman},
     utils::{parse_key_val, parse_key_val_opt},
 };
 
@@ -122,112 +120,17 @@ async fn run_container(
             .collect::<Vec<_>>()
     };
 
-    let labels = [
-        ("version", crate_version!()),
-        ("project", project_name),
-        ("service", &args.service),
-        ("oneoff", "true"),
-    ]
-    .into_iter()
-    .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
-    .collect::<Vec<_>>();
-    let pull_policy = service.pull_policy.as_ref().map(ToString::to_string);
-
-    let networks = service
-        .networks
-        .iter()
-        .map(|(name, network)| {
-            let name = file.networks[name].name.clone().unwrap();
-            let mut network = network
-                .as_ref()
-                .map(ToString::to_string)
-                .unwrap_or_default();
-
-            if let Some(mac_address) = service.mac_address.as_ref() {
-                if network.is_empty() {
-                    network = format!(":mac={mac_address}");
-                } else {
-                    network = format!("{network},mac={mac_address}");
-                }
-            }
-
-            format!("{name}{network}")
-        })
-        .collect::<Vec<_>>();
-    let volumes = service
-        .volumes
-        .iter()
-        .flat_map(|volume| {
-            let volume = match &volume.r#type {
-                ServiceVolumeType::Volume(Some(source)) => ServiceVolume {
-                    r#type: ServiceVolumeType::Volume(file.volumes[source].name.clone()),
-                    ..volume.clone()
-                },
-                _ => volume.clone(),
-            };
-
-            [
-                String::from(match volume.r#type {
-                    ServiceVolumeType::Volume(_) | ServiceVolumeType::Bind(_) => "--volume",
-                    ServiceVolumeType::Tmpfs => "--tmpfs",
-                }),
-                volume.to_string(),
-            ]
-        })
-        .collect::<Vec<_>>();
-    let secrets = service
-        .secrets
-        .iter()
-        .map(|secret| {
-            FileReference {
-                source: file.secrets[&secret.source].name.clone().unwrap(),
-                ..secret.clone()
-            }
-            .to_string()
-        })
-        .collect::<Vec<_>>();
-
-    let (global_args, service_args) = service.to_args();
-
-    podman
-        .attach(
-            global_args
-                .iter()
-                .map(AsRef::as_ref)
-                .chain([
-                    "run",
-                    "--interactive",
-                    "--pod",
-                    project_name,
-                    "--name",
-                    &container_name,
-                    "--network-alias",
-                    &args.service,
-                ])
-                .chain(
-                    requirements
-                        .iter()
-                        .flat_map(|requirement| ["--requires", requirement]),
-                )
-                .chain(labels.iter().flat_map(|label| ["--label", label]))
-                .chain(if let Some(pull_policy) = &pull_policy {
-                    vec!["--pull", pull_policy]
-                } else {
-                    vec![]
-                })
-                .chain(networks.iter().flat_map(|network| ["--network", network]))
-                .chain(volumes.iter().map(AsRef::as_ref))
-                .chain(secrets.iter().flat_map(|secret| ["--secret", secret]))
-                .chain(if args.detach {
-                    vec!["--detach"]
-                } else {
-                    vec![]
-                })
-                .chain(if args.rm { vec!["--rm"] } else { vec![] })
-                .chain(if args.no_tty { vec![] } else { vec!["--tty"] })
-                .chain(service_args.iter().map(AsRef::as_ref)),
-        )
-        .await
+    let mut spec = ContainerSpec::new(file, &args.service, service, container_name);
+
+    spec.requires = requirements;
+    spec.label("service", &args.service);
+    spec.label("oneoff", "true");
+    spec.interactive = true;
+    spec.detach = args.detach;
+    spec.rm = args.rm;
+    spec.tty = !args.no_tty;
+
+    podman.attach(spec.to_args("run")).await
 }
 
 pub(crate) async fn run(
@@ -236,6 +139,10 @@ pub(crate) async fn run(
     file: &Compose,
     config: &Config,
 ) -> Result<()> {
+    if let Backend::Kube = config.backend(file) {
+        bail!("`run` is not supported by the kube backend");
+    }
+
     let service = file
         .services
         .get(&args.service)
@@ -250,6 +157,7 @@ pub(crate) async fn run(
                 force_recreate: false,
                 no_recreate: false,
                 remove_orphans: args.remove_orphans,
+                plan: false,
             },
             podman,
             file,
@@ -1,11 +1,11 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use futures::{stream::FuturesUnordered, TryStreamExt};
 use indexmap::{IndexMap, IndexSet};
 use petgraph::{algo::has_path_connecting, graphmap::DiGraphMap, Direction};
 use tokio::sync::{broadcast, Barrier};
 
 use crate::{
-    compose::types::Compose,
+    compose::types::{Backend, Compose},
     config::Config,
     podman::Podman,
     progress::{Finish, Progress},
@@ -142,6 +142,10 @@ pub(crate) async fn run(
     file: &Compose,
     config: &Config,
 ) -> Result<()> {
+    if let Backend::Kube = config.backend(file) {
+        bail!("`start` is not supported by the kube backend");
+    }
+
     if args.services.is_empty()
         || !args
             .services
@@ -1,16 +1,18 @@
-use std::process;
+use std::{process, time::SystemTime};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use clap::crate_version;
 use futures::{stream::FuturesUnordered, TryStreamExt};
 use itertools::Itertools;
 use tokio::{select, signal};
 
 use crate::{
     commands::{
-        create::{self, PullPolicy},
+        convert::kube::{self, Kind},
+        create::{self, create_networks, PullPolicy},
         logs, start, stop,
     },
-    compose::types::Compose,
+    compose::types::{Backend, Compose},
     config::Config,
     podman::{types::Container, Podman},
     progress::{Finish, Progress},
@@ -54,6 +56,10 @@ pub(crate) struct Args {
     #[arg(long)]
     no_start: bool,
 
+    /// Show how the project would change, without applying anything
+    #[arg(long)]
+    plan: bool,
+
     /// Use this timeout in seconds for container shutdown when attached or when containers are already running [default: 10]
     #[arg(short, long)]
     timeout: Option<u32>,
@@ -100,30 +106,103 @@ async fn wait_containers(
         .map(|_| ())
 }
 
+/// Renders the project to Kubernetes YAML and replaces its pods through `podman kube play`
+pub(crate) async fn play(
+    podman: &Podman,
+    file: &Compose,
+    config: &Config,
+    start: bool,
+) -> Result<()> {
+    let name = file.name.as_ref().unwrap();
+    let x_podman = file.x_podman.clone().unwrap_or_default();
+    let unsupported = [
+        ("share", !x_podman.share.is_empty()),
+        ("infra", x_podman.infra.is_some()),
+        ("infra_image", x_podman.infra_image.is_some()),
+        ("infra_command", x_podman.infra_command.is_some()),
+        ("network", !x_podman.network.is_empty()),
+        ("hostname", x_podman.hostname.is_some()),
+        ("add_host", !x_podman.add_host.is_empty()),
+    ]
+    .into_iter()
+    .filter_map(|(option, set)| set.then_some(option))
+    .collect::<Vec<_>>();
+
+    // `podman kube play` creates one pod per Deployment, whose options it can't take
+    if !unsupported.is_empty() {
+        bail!(
+            "x-podman options {} are not supported by the kube backend",
+            unsupported.join(", ")
+        );
+    }
+
+    let labels = [("version", crate_version!()), ("project", name)]
+        .into_iter()
+        .map(|label| format!("io.podman.compose.{}={}", label.0, label.1))
+        .collect::<Vec<_>>();
+    let progress = Progress::new(config);
+
+    create_networks(podman, &progress, file, &labels).await?;
+
+    let spinner = progress.add_spinner(format!("Project {name}"), "Playing");
+    let networks = file
+        .networks
+        .values()
+        .filter_map(|network| network.name.as_ref())
+        .flat_map(|network| ["--network", network])
+        .collect::<Vec<_>>();
+
+    podman
+        .feed(
+            ["kube", "play", "--replace"]
+                .into_iter()
+                .chain(if start { vec![] } else { vec!["--start=false"] })
+                .chain(
+                    x_podman
+                        .userns
+                        .iter()
+                        .flat_map(|userns| ["--userns", userns]),
+                )
+                .chain(networks)
+                .chain(["-"]),
+            &kube::convert(file, Kind::Deployment)?,
+        )
+        .await
+        .finish_with_message(spinner, if start { "Started" } else { "Created" })?;
+
+    progress.finish();
+
+    Ok(())
+}
+
 pub(crate) async fn run(
     args: Args,
     podman: &Podman,
     file: &Compose,
     config: &Config,
 ) -> Result<()> {
-    create::run(
-        create::Args {
-            services: args.services.clone(),
-            pull: args.pull,
-            force_recreate: args.force_recreate,
-            no_recreate: args.no_recreate,
-            remove_orphans: args.remove_orphans,
-        },
-        podman,
-        file,
-        config,
-    )
-    .await?;
-
-    if !args.no_start {
-        start::run(
-            start::Args {
+    // Logs are replayed from here so that output produced before attaching is not lost
+    let since = humantime::format_rfc3339_nanos(SystemTime::now()).to_string();
+
+    if let Backend::Kube = config.backend(file) {
+        if !args.services.is_empty() {
+            bail!("Selecting services is not supported by the kube backend");
+        }
+
+        if args.plan {
+            bail!("`--plan` is not supported by the kube backend");
+        }
+
+        play(podman, file, config, !args.no_start).await?;
+    } else {
+        create::run(
+            create::Args {
                 services: args.services.clone(),
+                pull: args.pull,
+                force_recreate: args.force_recreate,
+                no_recreate: args.no_recreate,
+                remove_orphans: args.remove_orphans,
+                plan: args.plan,
             },
             podman,
             file,
@@ -131,95 +210,165 @@ pub(crate) async fn run(
         )
         .await?;
 
-        if args.wait || !args.detach {
-            let output = podman
-                .force_run([
-                    "ps",
-                    "--all",
-                    "--format",
-                    "json",
-                    "--filter",
-                    "label=io.podman.compose.oneoff=false",
-                    "--filter",
-                    &format!("pod={}", file.name.as_ref().unwrap()),
-                ])
-                .await?;
-            let containers = serde_json::from_str::<Vec<Container>>(&output)?
-                .into_iter()
-                .filter_map(|mut container| {
-                    container
-                        .labels
-                        .and_then(|labels| labels.service)
-                        .and_then(|service| {
-                            if args.services.contains(&service)
-                                || (args.services.is_empty()
-                                    && file.services.keys().contains(&service))
-                            {
-                                container.names.pop_front()
-                            } else {
-                                None
-                            }
-                        })
-                })
-                .collect::<Vec<_>>();
-
-            if !containers.is_empty() {
-                if args.wait {
-                    let progress = Progress::new(config);
-
-                    wait_containers(podman, &progress, &containers).await?;
-
-                    progress.finish();
-                } else {
-                    let mut services = if args.attach_dependencies {
-                        file.services.keys().cloned().collect()
-                    } else if !args.attach.is_empty() {
-                        args.attach
-                    } else if !args.services.is_empty() {
-                        args.services
-                    } else {
-                        file.services.keys().cloned().collect()
-                    };
+        if args.plan {
+            return Ok(());
+        }
 
-                    services.retain(|service| !args.no_attach.contains(service));
+        if !args.no_start {
+            start::run(
+                start::Args {
+                    services: args.services.clone(),
+                },
+                podman,
+                file,
+                config,
+            )
+            .await?;
+        }
+    }
 
-                    eprintln!("Attaching to {}", containers.join(", "));
+    if !args.no_start && (args.wait || !args.detach) {
+        let output = podman
+            .force_run([
+                "ps",
+                "--all",
+                "--format",
+                "json",
+                "--filter",
+                "label=io.podman.compose.oneoff=false",
+                "--filter",
+                &format!(
+                    "label=io.podman.compose.project={}",
+                    file.name.as_ref().unwrap()
+                ),
+            ])
+            .await?;
+        let containers = serde_json::from_str::<Vec<Container>>(&output)?
+            .into_iter()
+            .filter_map(|mut container| {
+                container
+                    .labels
+                    .and_then(|labels| labels.service)
+                    .and_then(|service| {
+                        if args.services.contains(&service)
+                            || (args.services.is_empty() && file.services.keys().contains(&service))
+                        {
+                            container.names.pop_front().map(|name| (service, name))
+                        } else {
+                            None
+                        }
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        if !containers.is_empty() {
+            if args.wait {
+                let progress = Progress::new(config);
+
+                wait_containers(
+                    podman,
+                    &progress,
+                    &containers
+                        .into_iter()
+                        .map(|(_, container)| container)
+                        .collect::<Vec<_>>(),
+                )
+                .await?;
 
-                    select! {
-                        biased;
+                progress.finish();
+            } else {
+                let mut services = if args.attach_dependencies {
+                    file.services.keys().cloned().collect()
+                } else if !args.attach.is_empty() {
+                    args.attach.clone()
+                } else if !args.services.is_empty() {
+                    args.services
+                } else {
+                    file.services.keys().cloned().collect::<Vec<_>>()
+                };
+
+                services.retain(|service| {
+                    !args.no_attach.contains(service)
+                        && (args.attach.contains(service)
+                            || file
+                                .services
+                                .get(service)
+                                .and_then(|service| service.attach)
+                                .unwrap_or(true))
+                });
+
+                if !services.is_empty() {
+                    eprintln!(
+                        "Attaching to {}",
+                        containers
+                            .iter()
+                            .filter_map(|(service, container)| {
+                                if services.contains(service) {
+                                    Some(container)
+                                } else {
+                                    None
+                                }
+                            })
+                            .join(", ")
+                    );
+                }
 
-                        _ = signal::ctrl_c() => {
-                            eprintln!("Gracefully stopping... (press Ctrl+C again to force)");
+                let attached = async {
+                    if services.is_empty() {
+                        // Nothing to show, but `up` stays attached until the containers stop,
+                        // streaming like logs do so that the wait neither holds a permit of
+                        // `--parallel` nor counts as a change of state
+                        let mut output =
+                            podman.watch(["wait"].into_iter().chain(
+                                containers.iter().map(|(_, container)| container.as_str()),
+                            ))?;
 
-                            stop::run(
-                                stop::Args {
-                                    services: Vec::new(),
-                                    timeout: args.timeout,
-                                },
-                                podman,
-                                file,
-                                config,
-                            )
-                            .await?;
+                        while output.try_next().await?.is_some() {}
 
-                            process::exit(130);
-                        }
-                        _ = logs::run(
+                        Ok(())
+                    } else {
+                        logs::run(
                             logs::Args {
                                 services,
                                 follow: true,
-                                since: None,
+                                since: Some(since),
                                 until: None,
                                 no_color: args.no_colour,
                                 no_log_prefix: args.no_log_prefix,
                                 timestamps: args.timestamps,
-                                tail: Some(0),
+                                tail: None,
+                                format: logs::Format::Text,
+                                grep: None,
+                                stream: None,
                             },
                             podman,
                             file,
-                        ) => {}
-                    };
-                }
+                        )
+                        .await
+                    }
+                };
+
+                select! {
+                    biased;
+
+                    _ = signal::ctrl_c() => {
+                        eprintln!("Gracefully stopping... (press Ctrl+C again to force)");
+
+                        stop::run(
+                            stop::Args {
+                                services: Vec::new(),
+                                timeout: args.timeout,
+                            },
+                            podman,
+                            file,
+                            config,
+                        )
+                        .await?;
+
+                        process::exit(130);
+                    }
+                    _ = attached => {}
+                };
             }
         }
     }
@@ -13,7 +13,7 @@ use std::{
     path::PathBuf,
 };
 
-use crate::utils::{regex, STYLED_WARNING};
+pub use crate::utils::STYLED_WARNING;
 use anyhow::{anyhow, bail, Context, Error, Result};
 use indexmap::IndexSet;
 use itertools::Itertools;
@@ -107,8 +107,8 @@ pub fn interpolate(value: &Value) -> Result<Value> {
 
 pub fn parse(
     project_name: &Option<String>,
-    files: &Vec<PathBuf>,
-    profiles: &Vec<String>,
+    files: &[PathBuf],
+    profiles: &[String],
     no_interpolate: bool,
 ) -> Result<Compose> {
     let contents = files
@@ -511,8 +511,8 @@ mod tests {
         assert_matches!(
             super::parse(
                 &None,
-                &vec![PathBuf::from(format!("../{}", resource))],
-                &vec![],
+                &[PathBuf::from(format!("../{}", resource))],
+                &[],
                 false
             ),
             Ok(_)
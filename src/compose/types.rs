@@ -3,6 +3,7 @@ use std::{
     fmt::{self, Display, Formatter},
     hash::{Hash, Hasher},
     path::{Path, PathBuf},
+    str::FromStr,
     time::Duration,
 };
 
@@ -38,6 +39,8 @@ pub struct Compose {
     #[serde_as(as = "IndexMap<_, DefaultOnNull>")]
     pub volumes: IndexMap<String, Volume>,
     pub secrets: IndexMap<String, Secret>,
+    #[serde(rename = "x-podman")]
+    pub x_podman: Option<PodmanExtension>,
 }
 
 impl Compose {
@@ -45,6 +48,13 @@ impl Compose {
         Self::default()
     }
 
+    pub fn has_pod(&self) -> bool {
+        self.x_podman
+            .as_ref()
+            .and_then(|x_podman| x_podman.pod)
+            .unwrap_or(true)
+    }
+
     pub fn merge(&mut self, other: Self) {
         if other.version.is_some() {
             self.version = other.version;
@@ -64,6 +74,94 @@ impl Compose {
         self.networks = other.networks;
         self.volumes = other.volumes;
         self.secrets = other.secrets;
+
+        if other.x_podman.is_some() {
+            self.x_podman = other.x_podman;
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[serde_as]
+#[serde_with::apply(
+    Vec => #[serde(skip_serializing_if = "Vec::is_empty", default)]
+)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct PodmanExtension {
+    pub backend: Option<Backend>,
+    pub pod: Option<bool>,
+    pub userns: Option<String>,
+    #[serde_as(as = "OneOrMany<_, PreferMany>")]
+    pub share: Vec<String>,
+    pub infra: Option<bool>,
+    pub infra_image: Option<String>,
+    pub infra_command: Option<String>,
+    #[serde_as(as = "OneOrMany<_, PreferMany>")]
+    pub network: Vec<String>,
+    pub hostname: Option<String>,
+    pub add_host: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Podman,
+    Kube,
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "podman" => Ok(Backend::Podman),
+            "kube" => Ok(Backend::Kube),
+            _ => bail!("Unknown backend \"{s}\""),
+        }
+    }
+}
+
+impl PodmanExtension {
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("--share"),
+            if self.share.is_empty() {
+                String::from("none")
+            } else {
+                self.share.join(",")
+            },
+        ];
+
+        if let Some(userns) = self.userns.as_ref().cloned() {
+            args.extend([String::from("--userns"), userns]);
+        }
+
+        if let Some(infra) = self.infra {
+            args.push(format!("--infra={infra}"));
+        }
+
+        if let Some(infra_image) = self.infra_image.as_ref().cloned() {
+            args.extend([String::from("--infra-image"), infra_image]);
+        }
+
+        if let Some(infra_command) = self.infra_command.as_ref().cloned() {
+            args.extend([String::from("--infra-command"), infra_command]);
+        }
+
+        for network in &self.network {
+            args.extend([String::from("--network"), network.clone()]);
+        }
+
+        if let Some(hostname) = self.hostname.as_ref().cloned() {
+            args.extend([String::from("--hostname"), hostname]);
+        }
+
+        for host in &self.add_host {
+            args.extend([String::from("--add-host"), host.clone()]);
+        }
+
+        args
     }
 }
 
@@ -76,6 +174,7 @@ impl Compose {
 )]
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Service {
+    pub attach: Option<bool>,
     pub blkio_config: Option<BlkioConfig>,
     pub cap_add: Vec<String>,
     pub cap_drop: Vec<String>,
@@ -900,6 +999,7 @@ impl Display for RestartPolicy {
 pub struct FileReference {
     #[serde_as(as = "DisplayFromAny")]
     pub source: String,
+    pub r#type: Option<FileReferenceType>,
     #[serde_as(as = "Option<DisplayFromAny>")]
     pub target: Option<String>,
     #[serde_as(as = "Option<DisplayFromAny>")]
@@ -928,6 +1028,10 @@ impl Display for FileReference {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let mut file_reference = vec![self.source.clone()];
 
+        if let Some(r#type) = &self.r#type {
+            file_reference.push(format!("type={type}"));
+        }
+
         if let Some(target) = &self.target {
             file_reference.push(format!("target={target}"));
         }
@@ -948,6 +1052,19 @@ impl Display for FileReference {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FileReferenceType {
+    Mount,
+    Env,
+}
+
+impl Display for FileReferenceType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", AsKebabCase(format!("{self:?}")))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum ResourceLimit {
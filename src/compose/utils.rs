@@ -5,7 +5,6 @@ use std::{
 
 use console::{style, StyledObject};
 use once_cell::sync::Lazy;
-pub(crate) use regex;
 use serde::{
     de::{self, Visitor},
     Deserializer, Serialize, Serializer,
@@ -19,8 +18,8 @@ macro_rules! regex {
     }};
 }
 
-pub(crate) static STYLED_WARNING: Lazy<StyledObject<&str>> =
-    Lazy::new(|| style("Warning:").yellow().bold());
+pub static STYLED_WARNING: Lazy<StyledObject<&str>> =
+    Lazy::new(|| style("Warning:").for_stderr().yellow().bold());
 
 pub(crate) struct DisplayFromAny;
 
@@ -1,9 +1,11 @@
 use std::{
     env,
+    ffi::OsString,
     path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
 use figment::{
     providers::{Env, Serialized},
     Figment,
@@ -11,8 +13,14 @@ use figment::{
 use itertools::iproduct;
 use once_cell::sync::Lazy;
 use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
 
-use crate::Flags;
+use crate::{
+    compose::types::{Backend, Compose},
+    podman::plan::PlanFormat,
+    progress::ProgressMode,
+    Flags,
+};
 
 static COMPOSE_FILE_NAMES: Lazy<Vec<String>> = Lazy::new(|| {
     iproduct!(["compose", "docker-compose"], ["yaml", "yml"])
@@ -20,6 +28,36 @@ static COMPOSE_FILE_NAMES: Lazy<Vec<String>> = Lazy::new(|| {
         .collect()
 });
 
+#[derive(ValueEnum, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Ansi {
+    Never,
+    Always,
+    #[default]
+    Auto,
+}
+
+impl Ansi {
+    fn apply(self) {
+        let enabled = match self {
+            Ansi::Never => false,
+            Ansi::Always => true,
+            Ansi::Auto => {
+                if env::var_os("NO_COLOR").is_some_and(|var| !var.is_empty()) {
+                    false
+                } else if env::var_os("CLICOLOR_FORCE").is_some_and(|var| var != "0") {
+                    true
+                } else {
+                    return;
+                }
+            }
+        };
+
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct Config {
     pub(crate) project_name: Option<String>,
@@ -27,7 +65,59 @@ pub(crate) struct Config {
     pub(crate) profiles: Vec<String>,
     pub(crate) env_file: PathBuf,
     pub(crate) project_directory: PathBuf,
+    pub(crate) progress: ProgressMode,
+    pub(crate) ansi: Ansi,
+    pub(crate) backend: Option<Backend>,
     pub(crate) dry_run: bool,
+    pub(crate) format: PlanFormat,
+    pub(crate) parallel_limit: Option<usize>,
+    pub(crate) podman_path: Option<PathBuf>,
+    pub(crate) connection: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) identity: Option<PathBuf>,
+}
+
+impl Config {
+    /// Resolves the backend given on the command line, falling back to the one of the Compose file
+    pub(crate) fn backend(&self, file: &Compose) -> Backend {
+        self.backend
+            .or_else(|| file.x_podman.as_ref().and_then(|x_podman| x_podman.backend))
+            .unwrap_or_default()
+    }
+
+    /// Podman binary, followed by the global options selecting the service it talks to
+    pub(crate) fn podman(&self) -> Vec<OsString> {
+        [self
+            .podman_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("podman"))
+            .into_os_string()]
+        .into_iter()
+        .chain(
+            [
+                ("--connection", self.connection.as_ref().map(OsString::from)),
+                ("--url", self.url.as_ref().map(OsString::from)),
+                ("--identity", self.identity.as_ref().map(OsString::from)),
+            ]
+            .into_iter()
+            .filter_map(|(option, value)| value.map(|value| [OsString::from(option), value]))
+            .flatten(),
+        )
+        .collect()
+    }
+
+    /// URL of the Podman service, which Podman otherwise takes from `CONTAINER_HOST`
+    pub(crate) fn host(&self) -> Option<String> {
+        self.url
+            .clone()
+            .or_else(|| env::var("CONTAINER_HOST").ok())
+            .filter(|host| !host.is_empty())
+    }
+
+    /// Whether Podman runs on another machine, where the paths of this one don't exist
+    pub(crate) fn remote(&self) -> bool {
+        self.connection.is_some() || self.host().is_some_and(|host| !host.starts_with("unix://"))
+    }
 }
 
 fn find(directory: &Path, files: &[String]) -> Result<PathBuf> {
@@ -52,10 +142,29 @@ fn find(directory: &Path, files: &[String]) -> Result<PathBuf> {
 fn resolve(flags: &Flags) -> Result<Config> {
     let current_dir = env::current_dir()?;
     let flags = Figment::new()
-        .merge(Env::prefixed("COMPOSE_").ignore(&["env_file", "project_directory", "dry_run"]))
+        .merge(Env::prefixed("COMPOSE_").ignore(&[
+            "env_file",
+            "project_directory",
+            "dry_run",
+            "format",
+        ]))
         .merge(Serialized::defaults(flags))
         .extract::<Flags>()?;
 
+    let parallel_limit = match flags.parallel_limit {
+        None | Some(-1) => None,
+        Some(limit) if limit > 0 => Some(limit as usize),
+        Some(limit) => bail!("Invalid parallel limit {limit}: expected a positive number, or -1"),
+    };
+
+    if flags.format.is_some() && !flags.dry_run.unwrap_or_default() {
+        bail!("`--format` can only be used with `--dry-run`");
+    }
+
+    if flags.connection.is_some() && flags.url.is_some() {
+        bail!("`--connection` and `--url` cannot be used together");
+    }
+
     let files = if let Some(files) = flags.file {
         files
             .into_iter()
@@ -68,29 +177,37 @@ fn resolve(flags: &Flags) -> Result<Config> {
             })
             .collect::<Result<Vec<_>, _>>()?
     } else {
-        let file = find(
+        match find(
             flags.project_directory.as_ref().unwrap_or(&current_dir),
             &COMPOSE_FILE_NAMES,
-        )?;
-
-        let override_file = file.with_extension(if let Some(extension) = file.extension() {
-            format!("override.{}", extension.to_string_lossy())
-        } else {
-            String::from("override")
-        });
-
-        if override_file.is_file() {
-            vec![&file, &override_file]
-        } else {
-            vec![&file]
+        ) {
+            Ok(file) => {
+                let override_file =
+                    file.with_extension(if let Some(extension) = file.extension() {
+                        format!("override.{}", extension.to_string_lossy())
+                    } else {
+                        String::from("override")
+                    });
+
+                if override_file.is_file() {
+                    vec![&file, &override_file]
+                } else {
+                    vec![&file]
+                }
+                .into_iter()
+                .map(|file| file.absolutize_from(&current_dir).map(PathBuf::from))
+                .collect::<Result<Vec<_>, _>>()?
+            }
+            // The project can still be found through the labels of its pod
+            Err(_) if flags.project_name.is_some() => Vec::new(),
+            Err(err) => return Err(err),
         }
-        .into_iter()
-        .map(|file| file.absolutize_from(&current_dir).map(PathBuf::from))
-        .collect::<Result<Vec<_>, _>>()?
     };
 
     let project_directory = if let Some(dir) = flags.project_directory {
         dir.absolutize_from(&current_dir)?.to_path_buf()
+    } else if files.is_empty() {
+        current_dir
     } else {
         files[0]
             .parent()
@@ -104,7 +221,18 @@ fn resolve(flags: &Flags) -> Result<Config> {
         files,
         profiles: flags.profile.unwrap_or_default(),
         project_directory,
+        progress: flags.progress.unwrap_or_default(),
+        ansi: flags.ansi.unwrap_or_default(),
+        backend: flags.backend,
         dry_run: flags.dry_run.unwrap_or_default(),
+        format: flags.format.unwrap_or_default(),
+        parallel_limit,
+        podman_path: flags
+            .podman_path
+            .or_else(|| env::var_os("PODMAN_BINARY").map(PathBuf::from)),
+        connection: flags.connection,
+        url: flags.url,
+        identity: flags.identity,
         ..Config::default()
     })
 }
@@ -128,6 +256,7 @@ pub(crate) fn load(flags: Flags) -> Result<Config> {
 
     let mut config = resolve(&flags)?;
     config.env_file = env_file;
+    config.ansi.apply();
 
     Ok(config)
 }
@@ -0,0 +1,203 @@
+use std::{convert::Infallible, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use console::measure_text_width;
+use heck::ToShoutySnakeCase;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+static FIELD: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*(json\s+)?\.(\w*)\s*\}\}").unwrap());
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Text(String),
+    Field(String),
+    Json(String),
+}
+
+/// A subset of Go templates, supporting `{{.Field}}` and `{{json .Field}}` actions
+#[derive(Clone, Debug)]
+pub(crate) struct Template(Vec<Segment>);
+
+impl FromStr for Template {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.replace(r"\t", "\t").replace(r"\n", "\n");
+        let mut segments = Vec::new();
+        let mut end = 0;
+
+        for captures in FIELD.captures_iter(&s) {
+            let action = captures.get(0).unwrap();
+            let field = captures[2].to_string();
+
+            if action.start() > end {
+                segments.push(Segment::Text(s[end..action.start()].to_string()));
+            }
+
+            segments.push(if captures.get(1).is_some() {
+                Segment::Json(field)
+            } else {
+                Segment::Field(field)
+            });
+            end = action.end();
+        }
+
+        if end < s.len() {
+            segments.push(Segment::Text(s[end..].to_string()));
+        }
+
+        Ok(Self(segments))
+    }
+}
+
+fn lookup<'a>(row: &'a Value, field: &str) -> Result<&'a Value> {
+    if field.is_empty() {
+        Ok(row)
+    } else {
+        row.get(field)
+            .ok_or_else(|| anyhow!("Can't evaluate field {field} in template"))
+    }
+}
+
+fn to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl Template {
+    fn render(&self, row: &Value) -> Result<String> {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => Ok(text.clone()),
+                Segment::Field(field) => lookup(row, field).map(to_string),
+                Segment::Json(field) => Ok(lookup(row, field)?.to_string()),
+            })
+            .collect()
+    }
+
+    fn columns(&self) -> Vec<(String, String)> {
+        self.0
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Field(field) | Segment::Json(field) => Some((
+                    field.to_shouty_snake_case().replace('_', " "),
+                    field.clone(),
+                )),
+                Segment::Text(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Output format of listing commands: `table`, `json`, `table <template>` or `<template>`
+#[derive(Clone, Debug)]
+pub(crate) enum Format {
+    Table(Option<Template>),
+    Json,
+    Template(Template),
+}
+
+impl FromStr for Format {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "table" => Self::Table(None),
+            "json" => Self::Json,
+            _ => match s.strip_prefix("table ") {
+                Some(template) => Self::Table(Some(template.parse()?)),
+                None => Self::Template(s.parse()?),
+            },
+        })
+    }
+}
+
+impl Format {
+    /// Prints the rows, using `columns` as (header, field) pairs when no table template is given
+    pub(crate) fn print<T>(&self, columns: &[(&str, &str)], rows: &[T]) -> Result<()>
+    where
+        T: Serialize,
+    {
+        let values = || {
+            rows.iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        match self {
+            Self::Table(template) => {
+                let columns = template.as_ref().map_or_else(
+                    || {
+                        columns
+                            .iter()
+                            .map(|(header, field)| (header.to_string(), field.to_string()))
+                            .collect()
+                    },
+                    Template::columns,
+                );
+                let table = values()?
+                    .iter()
+                    .map(|row| {
+                        columns
+                            .iter()
+                            .map(|(_, field)| lookup(row, field).map(to_string))
+                            .collect::<Result<Vec<_>>>()
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                print_table(
+                    &columns
+                        .into_iter()
+                        .map(|(header, _)| header)
+                        .collect::<Vec<_>>(),
+                    &table,
+                );
+            }
+            Self::Json => println!("{}", serde_json::to_string(rows)?),
+            Self::Template(template) => {
+                for row in values()? {
+                    println!("{}", template.render(&row)?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn print_table(headers: &[String], rows: &[Vec<String>]) {
+    let widths = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| measure_text_width(&row[i]))
+                .chain([measure_text_width(header)])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    for row in [headers.to_vec()].iter().chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                format!(
+                    "{cell}{}",
+                    " ".repeat(width.saturating_sub(measure_text_width(cell)))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        println!("{}", line.trim_end());
+    }
+}
@@ -1,20 +1,27 @@
 mod commands;
 mod config;
+mod format;
 mod podman;
 mod progress;
 mod utils;
 
-use std::{env, path::PathBuf};
+use std::{env, ffi::OsString, path::PathBuf};
 
 use anyhow::Result;
-use clap::{ArgAction, Parser};
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser as _},
+    ArgAction, CommandFactory, FromArgMatches, Parser,
+};
 pub(crate) use haddock_compose_parser as compose;
 use serde::{Deserialize, Serialize};
 use serde_with::{
     formats::CommaSeparator, serde_as, skip_serializing_none, PickFirst, StringWithSeparator,
 };
 
-use self::{commands::Command, utils::PathSeparator};
+use self::{
+    commands::Command, compose::types::Backend, config::Ansi, podman::plan::PlanFormat,
+    progress::ProgressMode, utils::PathSeparator,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, next_display_order = None)]
@@ -56,16 +63,117 @@ pub(crate) struct Flags {
     #[arg(skip)]
     pub(crate) path_separator: Option<String>,
 
+    /// Set type of progress output
+    #[arg(long, value_enum)]
+    pub(crate) progress: Option<ProgressMode>,
+
+    /// Control when to print ANSI control characters
+    #[arg(long, value_enum)]
+    pub(crate) ansi: Option<Ansi>,
+
+    /// Run the project with individual Podman commands, or through `podman kube play`
+    #[arg(long, value_parser = PossibleValuesParser::new(["podman", "kube"]).map(|backend| backend.parse::<Backend>().unwrap()))]
+    pub(crate) backend: Option<Backend>,
+
     /// Only show the Podman commands that will be executed
     #[arg(long, action = ArgAction::SetTrue, global = true)]
     pub(crate) dry_run: Option<bool>,
+
+    /// Control max parallelism, -1 for unlimited
+    #[arg(long = "parallel", allow_negative_numbers = true)]
+    pub(crate) parallel_limit: Option<i64>,
+
+    /// Format of the plan shown by a dry run
+    #[arg(long, value_enum)]
+    pub(crate) format: Option<PlanFormat>,
+
+    /// Path of the Podman binary
+    #[arg(long)]
+    pub(crate) podman_path: Option<PathBuf>,
+
+    /// Podman system connection to use
+    #[arg(long)]
+    pub(crate) connection: Option<String>,
+
+    /// URL of the Podman service to use
+    #[arg(long)]
+    pub(crate) url: Option<String>,
+
+    /// Path of the SSH identity file to connect with
+    #[arg(long)]
+    pub(crate) identity: Option<PathBuf>,
+}
+
+/// Parses the command line, where `--format` may also follow the subcommand like `--dry-run`
+fn parse<I, T>(itr: I) -> Result<Args, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let mut command = Args::command();
+
+    // A global argument would clash with the subcommands that have a `--format` of their own
+    let format = command
+        .get_arguments()
+        .find(|arg| arg.get_id() == "format")
+        .cloned()
+        .unwrap();
+    let subcommands = command
+        .get_subcommands()
+        .filter(|subcommand| {
+            subcommand
+                .get_arguments()
+                .all(|arg| arg.get_id() != "format")
+        })
+        .map(|subcommand| subcommand.get_name().to_owned())
+        .collect::<Vec<_>>();
+
+    for subcommand in &subcommands {
+        command = command.mut_subcommand(subcommand, |subcommand| subcommand.arg(format.clone()));
+    }
+
+    let matches = command.try_get_matches_from(itr)?;
+    let mut args = Args::from_arg_matches(&matches)?;
+
+    if let Some(format) = matches
+        .subcommand()
+        .filter(|(name, _)| subcommands.iter().any(|subcommand| subcommand == name))
+        .and_then(|(_, matches)| matches.get_one::<PlanFormat>("format"))
+    {
+        args.flags.format = Some(*format);
+    }
+
+    Ok(args)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = parse(env::args_os()).unwrap_or_else(|error| error.exit());
     let config = config::load(args.flags)?;
 
     env::set_current_dir(&config.project_directory)?;
     commands::run(args.command, config).await
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn format_after_subcommand() {
+        let args = parse(["haddock", "up", "--dry-run", "--format", "script"]).unwrap();
+
+        assert_eq!(args.flags.dry_run, Some(true));
+        assert_eq!(args.flags.format, Some(PlanFormat::Script));
+    }
+
+    #[test]
+    fn format_of_subcommand() {
+        let args = parse(["haddock", "--format", "json", "events", "--format", "json"]).unwrap();
+
+        assert_eq!(args.flags.format, Some(PlanFormat::Json));
+        assert!(parse(["haddock", "events", "--format", "script"]).is_err());
+    }
+}
@@ -1,46 +1,161 @@
 automod::dir!(pub(crate) "src/podman");
 
 use std::{
-    ffi::OsStr,
-    path::PathBuf,
+    cell::{Cell, RefCell},
+    ffi::{OsStr, OsString},
+    path::Path,
     pin::Pin,
-    process::{self, Stdio},
 };
 
-use anyhow::{anyhow, bail, Context, Error, Result};
-use futures::{
-    stream::{self, select},
-    Stream, StreamExt, TryStreamExt,
-};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
+use indexmap::{IndexMap, IndexSet};
 use once_cell::sync::Lazy;
-use tokio::{
-    io::{AsyncBufReadExt, BufReader},
-    process::Command,
-};
-use tokio_stream::wrappers::LinesStream;
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
 
-use self::types::Version;
+use self::{
+    api::{Api, Client},
+    cli::Cli,
+    plan::{classify, Action, Plan, Resource},
+    spec::Payload,
+    types::{Container, Network, OutputStream, Pod, Secret, Volume},
+};
 use crate::config::Config;
 
+pub(crate) type Lines = Pin<Box<dyn Stream<Item = Result<(OutputStream, String)>>>>;
+
 static PODMAN_MIN_SUPPORTED_VERSION: Lazy<semver::Version> =
     Lazy::new(|| semver::Version::new(4, 3, 0));
 
+/// Runs podman invocations, whether through the CLI, the REST API or a fake in tests
+#[async_trait(?Send)]
+pub(crate) trait Executor {
+    /// Runs the invocation to completion, returning its stdout
+    async fn run(&self, args: Vec<OsString>) -> Result<String>;
+
+    /// Runs a creation, either from its CLI arguments or from its API payload
+    async fn create(&self, args: Vec<OsString>, _payload: Payload) -> Result<String> {
+        self.run(args).await
+    }
+
+    /// Runs the invocation with `input` written to its stdin, returning its stdout
+    async fn feed(&self, args: Vec<OsString>, input: &str) -> Result<String>;
+
+    /// Streams the stdout and stderr lines of the invocation
+    fn watch(&self, args: Vec<OsString>) -> Result<Lines>;
+
+    /// Pipes the stdout of the `source` invocation into the stdin of the `destination` one
+    async fn pipe(&self, source: Vec<OsString>, destination: Vec<OsString>) -> Result<()>;
+
+    /// Runs the invocation attached to the terminal
+    async fn attach(&self, args: Vec<OsString>) -> Result<()>;
+
+    /// Creates a directory on the host of the containers, such as the source of a bind mount
+    fn create_dir(&self, path: &Path) -> Result<()>;
+}
+
+fn collect<I, S>(args: I) -> Vec<OsString>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    args.into_iter()
+        .map(|arg| arg.as_ref().to_os_string())
+        .collect()
+}
+
+fn strings(args: &[OsString]) -> Vec<String> {
+    args.iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+fn join(args: &[OsString]) -> String {
+    shell_words::join(strings(args))
+}
+
+/// Kind of resource that a read-only query lists, if any
+fn scope(args: &[OsString]) -> Option<Resource> {
+    match args.first()?.to_str()? {
+        "ps" | "container" => Some(Resource::Container),
+        "pod" => Some(Resource::Pod),
+        "network" => Some(Resource::Network),
+        "volume" => Some(Resource::Volume),
+        "secret" => Some(Resource::Secret),
+        "images" | "image" => Some(Resource::Image),
+        _ => None,
+    }
+}
+
+/// Whether changing a resource of one kind can change those of the other, as pods hold containers
+fn related(changed: Resource, listed: Resource) -> bool {
+    changed == listed
+        || matches!(
+            (changed, listed),
+            (Resource::Kube, _)
+                | (Resource::Pod, Resource::Container)
+                | (Resource::Container, Resource::Pod)
+        )
+}
+
+/// Restores the reason of the plan that [`Podman::reason`] replaced when dropped
+pub(crate) struct Reason<'a> {
+    plan: Option<&'a Plan>,
+    previous: Option<String>,
+}
+
+impl Drop for Reason<'_> {
+    fn drop(&mut self) {
+        if let Some(plan) = self.plan {
+            plan.set_reason(self.previous.take());
+        }
+    }
+}
+
 pub(crate) struct Podman {
-    project_directory: PathBuf,
-    dry_run: bool,
+    executor: Box<dyn Executor>,
+    /// Podman binary and its global options, as shown by a dry run
+    program: String,
+    remote: bool,
+    plan: Option<Plan>,
+    /// Bounds the invocations in flight, each holding a permit only while it runs
+    limit: Option<Semaphore>,
+    /// Outputs of the read-only queries, reused until an invocation changes the state
+    queries: RefCell<IndexMap<Vec<OsString>, String>>,
+    /// Number of invocations that changed the state, so that queries which were in flight meanwhile
+    /// aren't kept
+    generation: Cell<u64>,
+    /// Names of the existing resources of each kind, listed once for all the probes
+    names: Mutex<IndexMap<Resource, IndexSet<String>>>,
 }
 
 impl Podman {
     pub(crate) async fn new(config: &Config) -> Result<Self> {
-        let podman = Self {
-            project_directory: config.project_directory.clone(),
-            dry_run: config.dry_run,
+        let cli = Cli::new(config);
+
+        // Talk to the REST API when its socket answers, and fall back to the CLI otherwise, which
+        // a chosen binary or connection always goes through
+        let socket = if config.podman_path.is_some() || config.connection.is_some() {
+            None
+        } else {
+            Client::socket(config.host().as_deref())
+        };
+        let (executor, version): (Box<dyn Executor>, _) = match socket {
+            Some(socket) => match Client::connect(socket).await {
+                Ok((client, version)) => (Box::new(Api::new(client, cli)), version),
+                Err(_) => {
+                    let version = cli.version().await?;
+
+                    (Box::new(cli), version)
+                }
+            },
+            None => {
+                let version = cli.version().await?;
+
+                (Box::new(cli), version)
+            }
         };
-        let output = podman.force_run(["version", "--format", "json"]).await?;
-        let version = serde_json::from_str::<Version>(&output)
-            .with_context(|| anyhow!("Podman version not recognised"))?
-            .client
-            .version;
 
         if version < *PODMAN_MIN_SUPPORTED_VERSION {
             bail!(
@@ -49,106 +164,280 @@ impl Podman {
             );
         }
 
-        Ok(podman)
+        Ok(Self::with_executor(executor, config))
+    }
+
+    /// Wraps `executor`, only recording the mutations for a dry run
+    pub(crate) fn with_executor(executor: Box<dyn Executor>, config: &Config) -> Self {
+        Self {
+            executor,
+            program: join(&config.podman()),
+            remote: config.remote(),
+            plan: config.dry_run.then(|| Plan::new(config.format)),
+            limit: config.parallel_limit.map(Semaphore::new),
+            queries: RefCell::new(IndexMap::new()),
+            generation: Cell::new(0),
+            names: Mutex::new(IndexMap::new()),
+        }
+    }
+
+    /// Waits for an invocation to be allowed to run under the parallel limit
+    async fn permit(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.limit {
+            Some(limit) => limit.acquire().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Forgets the state that an executed invocation may have changed
+    async fn changed(&self, args: &[OsString], success: bool) {
+        let mut names = self.names.lock().await;
+        let classified = classify(&strings(args));
+
+        self.generation.set(self.generation.get() + 1);
+
+        let affected = classified.as_ref().map(|(_, resource, _)| *resource);
+
+        self.queries
+            .borrow_mut()
+            .retain(|query, _| match (affected, scope(query)) {
+                (Some(affected), Some(scope)) => !related(affected, scope),
+                _ => false,
+            });
+
+        match classified {
+            Some((Action::Create, resource, Some(name))) if success => {
+                if let Some(names) = names.get_mut(&resource) {
+                    names.insert(name);
+                }
+            }
+            Some((
+                Action::Start
+                | Action::Stop
+                | Action::Restart
+                | Action::Kill
+                | Action::Pause
+                | Action::Unpause
+                | Action::Copy,
+                ..,
+            )) => {}
+            // Removals can cascade, such as `rm --depend` or `pod rm`
+            Some((_, affected, _)) => names.retain(|resource, _| !related(affected, *resource)),
+            None => names.clear(),
+        }
     }
 
-    fn command<I, S>(&self, args: I) -> Command
+    /// Whether a resource exists, from a listing of its kind fetched once and kept up to date
+    pub(crate) async fn exists(&self, resource: Resource, name: &str) -> Result<bool> {
+        if self
+            .plan
+            .as_ref()
+            .is_some_and(|plan| plan.removed(resource, name))
+        {
+            return Ok(false);
+        }
+
+        let mut names = self.names.lock().await;
+
+        if !names.contains_key(&resource) {
+            let listing = match resource {
+                Resource::Container => serde_json::from_str::<Vec<Container>>(
+                    &self.force_run(["ps", "--all", "--format", "json"]).await?,
+                )?
+                .into_iter()
+                .flat_map(|container| container.names)
+                .collect(),
+                Resource::Pod => serde_json::from_str::<Vec<Pod>>(
+                    &self.force_run(["pod", "ps", "--format", "json"]).await?,
+                )?
+                .into_iter()
+                .map(|pod| pod.name)
+                .collect(),
+                Resource::Network => serde_json::from_str::<Vec<Network>>(
+                    &self
+                        .force_run(["network", "ls", "--format", "json"])
+                        .await?,
+                )?
+                .into_iter()
+                .map(|network| network.name)
+                .collect(),
+                Resource::Volume => serde_json::from_str::<Vec<Volume>>(
+                    &self.force_run(["volume", "ls", "--format", "json"]).await?,
+                )?
+                .into_iter()
+                .map(|volume| volume.name)
+                .collect(),
+                Resource::Secret => serde_json::from_str::<Option<Vec<Secret>>>(
+                    &self.force_run(["secret", "ls", "--format", "json"]).await?,
+                )?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|secret| secret.spec.name)
+                .collect(),
+                _ => bail!("Existence of a {resource} cannot be probed"),
+            };
+
+            names.insert(resource, listing);
+        }
+
+        Ok(names[&resource].contains(name))
+    }
+
+    pub(crate) async fn run<I, S>(&self, args: I) -> Result<String>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        let mut command = Command::new("podman");
-        command.current_dir(&self.project_directory).args(args);
+        let args = collect(args);
+
+        if let Some(plan) = &self.plan {
+            plan.command(
+                &strings(&args),
+                format!("{} {}", self.program, join(&args)),
+                None,
+            );
 
-        command
+            Ok(String::new())
+        } else {
+            let output = {
+                let _permit = self.permit().await;
+
+                self.executor.run(args.clone()).await
+            };
+
+            self.changed(&args, output.is_ok()).await;
+
+            output
+        }
     }
 
-    pub(crate) async fn run<I, S>(&self, args: I) -> Result<String>
+    /// Runs a creation like [`Podman::run`], letting the executor send its API payload if any
+    pub(crate) async fn create<I, S>(&self, args: I, payload: Option<Payload>) -> Result<String>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        if self.dry_run {
-            println!(
-                "`podman {}`",
-                shell_words::join(
-                    args.into_iter()
-                        .map(|arg| arg.as_ref().to_string_lossy().to_string())
-                )
+        let Some(payload) = payload.filter(|_| self.plan.is_none()) else {
+            return self.run(args).await;
+        };
+        let args = collect(args);
+        let output = {
+            let _permit = self.permit().await;
+
+            self.executor.create(args.clone(), payload).await
+        };
+
+        self.changed(&args, output.is_ok()).await;
+
+        output
+    }
+
+    pub(crate) async fn force_run<I, S>(&self, args: I) -> Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args = collect(args);
+
+        if let Some(output) = self.queries.borrow().get(&args) {
+            return Ok(output.clone());
+        }
+
+        let generation = self.generation.get();
+        let output = {
+            let _permit = self.permit().await;
+
+            self.executor.run(args.clone()).await?
+        };
+
+        if self.generation.get() == generation {
+            self.queries.borrow_mut().insert(args, output.clone());
+        }
+
+        Ok(output)
+    }
+
+    pub(crate) async fn feed<I, S>(&self, args: I, input: &str) -> Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args = collect(args);
+
+        if let Some(plan) = &self.plan {
+            plan.command(
+                &strings(&args),
+                format!("{} {}", self.program, join(&args)),
+                Some(input),
             );
 
             Ok(String::new())
         } else {
-            self.force_run(args).await
+            let output = {
+                let _permit = self.permit().await;
+
+                self.executor.feed(args.clone(), input).await
+            };
+
+            self.changed(&args, output.is_ok()).await;
+
+            output
         }
     }
 
-    pub(crate) async fn force_run<I, S>(&self, args: I) -> Result<String>
+    pub(crate) fn watch<I, S>(&self, args: I) -> Result<Lines>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        let mut command = self.command(args);
-
-        let output = command.output().await.with_context(|| {
-            anyhow!(
-                "`{} {}` cannot be executed",
-                command.as_std().get_program().to_string_lossy(),
-                shell_words::join(
-                    command
-                        .as_std()
-                        .get_args()
-                        .map(|arg| arg.to_string_lossy().to_string())
-                )
-            )
-        })?;
-
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        let args = collect(args);
+
+        if let Some(plan) = &self.plan {
+            plan.command(
+                &strings(&args),
+                format!("{} {}", self.program, join(&args)),
+                None,
+            );
+
+            Ok(stream::empty().boxed())
         } else {
-            Err(
-                anyhow!("{}", String::from_utf8_lossy(&output.stderr)).context(anyhow!(
-                    "`{} {}` returned an error",
-                    command.as_std().get_program().to_string_lossy(),
-                    shell_words::join(
-                        command
-                            .as_std()
-                            .get_args()
-                            .map(|arg| arg.to_string_lossy().to_string())
-                    )
-                )),
-            )
-        }
-    }
-
-    pub(crate) fn watch<I, S>(&self, args: I) -> Result<Pin<Box<dyn Stream<Item = Result<String>>>>>
+            self.executor.watch(args)
+        }
+    }
+
+    pub(crate) async fn pipe<I, S, J, T>(&self, source: I, destination: J) -> Result<()>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
+        J: IntoIterator<Item = T>,
+        T: AsRef<OsStr>,
     {
-        if self.dry_run {
-            println!(
-                "`podman {}`",
-                shell_words::join(
-                    args.into_iter()
-                        .map(|arg| arg.as_ref().to_string_lossy().to_string())
-                )
+        let source = collect(source);
+        let destination = collect(destination);
+
+        if let Some(plan) = &self.plan {
+            plan.command(
+                &strings(&source),
+                format!(
+                    "{0} {1} | {0} {2}",
+                    self.program,
+                    join(&source),
+                    join(&destination)
+                ),
+                None,
             );
 
-            Ok(stream::empty().boxed())
+            Ok(())
         } else {
-            let child = self
-                .command(args)
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()?;
-            let stdout = BufReader::new(child.stdout.unwrap()).lines();
-            let stderr = BufReader::new(child.stderr.unwrap()).lines();
+            let result = {
+                let _permit = self.permit().await;
+
+                self.executor.pipe(source, destination.clone()).await
+            };
 
-            Ok(select(LinesStream::new(stdout), LinesStream::new(stderr))
-                .map_err(Error::from)
-                .boxed())
+            self.changed(&destination, result.is_ok()).await;
+
+            result
         }
     }
 
@@ -157,24 +446,105 @@ impl Podman {
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        if self.dry_run {
-            println!(
-                "`podman {}`",
-                shell_words::join(
-                    args.into_iter()
-                        .map(|arg| arg.as_ref().to_string_lossy().to_string())
-                )
+        let args = collect(args);
+
+        if let Some(plan) = &self.plan {
+            plan.command(
+                &strings(&args),
+                format!("{} {}", self.program, join(&args)),
+                None,
             );
 
             Ok(())
         } else {
-            let status = self.command(args).spawn()?.wait().await?;
+            let result = self.executor.attach(args.clone()).await;
 
-            if !status.success() {
-                process::exit(status.code().unwrap_or(1));
+            self.changed(&args, result.is_ok()).await;
+
+            result
+        }
+    }
+
+    pub(crate) fn create_dir(&self, path: &Path) -> Result<()> {
+        if let Some(plan) = &self.plan {
+            // A remote host can't run the script's `mkdir`, while creating them here is pointless
+            if !self.remote {
+                plan.directory(&path.to_string_lossy());
             }
 
             Ok(())
+        } else {
+            self.executor.create_dir(path)
+        }
+    }
+
+    /// Records in the plan of a dry run that a resource is left as it is
+    pub(crate) fn keep(&self, resource: Resource, name: &str, reason: &str) {
+        if let Some(plan) = &self.plan {
+            plan.keep(resource, name, reason);
+        }
+    }
+
+    /// Gives a reason in the plan of a dry run to the mutations until the returned guard is
+    /// dropped, including on an early return
+    pub(crate) fn reason(&self, reason: &str) -> Reason<'_> {
+        Reason {
+            previous: self
+                .plan
+                .as_ref()
+                .and_then(|plan| plan.set_reason(Some(reason.to_string()))),
+            plan: self.plan.as_ref(),
         }
     }
+
+    /// Prints the plan of a dry run, for the formats that defer it to the end of the command
+    pub(crate) fn print_plan(&self) -> Result<()> {
+        if let Some(plan) = self.plan.as_ref().map(Plan::render).transpose()?.flatten() {
+            print!("{plan}");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::podman::{fake::Fake, plan::PlanFormat};
+
+    #[test]
+    fn reason_scope() {
+        let config = Config {
+            dry_run: true,
+            format: PlanFormat::Json,
+            ..Config::default()
+        };
+        let podman = Podman::with_executor(Box::new(Fake::default()), &config);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // An error leaving the scope early must not leave its reason to the following mutations
+        let result = runtime.block_on(async {
+            let _reason = podman.reason("secret content changed");
+
+            podman.run(["secret", "rm", "proj_token"]).await?;
+
+            Err::<(), _>(anyhow!("Interrupted"))
+        });
+
+        runtime
+            .block_on(podman.run(["network", "create", "proj_default"]))
+            .unwrap();
+
+        let steps = serde_json::from_str::<serde_json::Value>(
+            &podman.plan.as_ref().unwrap().render().unwrap().unwrap(),
+        )
+        .unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(steps[0]["reason"], "secret content changed");
+        assert_eq!(steps[1]["reason"], "does not exist");
+    }
 }
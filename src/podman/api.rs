@@ -0,0 +1,1248 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+};
+
+use super::{cli::Cli, spec::Payload, types::OutputStream, Executor, Lines};
+use crate::utils::parse_timestamp;
+
+const API_VERSION: &str = "v4.0.0";
+
+/// A podman invocation answered by the REST API
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum Route {
+    /// Probe answered by the status code alone
+    Exists(String),
+    /// Listing whose response body, once converted to the schema of the CLI, is the output of the
+    /// command
+    List(String),
+    /// Container action, printing the name of the container like the CLI does
+    Action(String, String),
+    /// Container logs, multiplexed into stdout and stderr frames unless the container has a TTY
+    Logs(String, String),
+    /// Event stream, in the Docker event schema
+    Events(String),
+}
+
+impl Route {
+    pub(crate) fn is_stream(&self) -> bool {
+        matches!(self, Route::Logs(..) | Route::Events(_))
+    }
+}
+
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Encodes the `--filter` flags of a command as the `filters` parameter of the API
+fn filters(filters: &IndexMap<&str, Vec<&str>>) -> String {
+    format!(
+        "filters={}",
+        encode(&serde_json::to_string(filters).unwrap())
+    )
+}
+
+/// Parses the flags of a listing command, which must request JSON output
+fn list(path: &str, args: &[&str]) -> Option<Route> {
+    let mut filter_map = IndexMap::<_, Vec<_>>::new();
+    let mut all = false;
+    let mut json = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--all" => all = true,
+            "--format" => json = *args.next()? == "json",
+            "--filter" => {
+                let (key, value) = args.next()?.split_once('=')?;
+
+                filter_map.entry(key).or_default().push(value);
+            }
+            _ => return None,
+        }
+    }
+
+    if !json {
+        return None;
+    }
+
+    let query = all
+        .then(|| String::from("all=true"))
+        .into_iter()
+        .chain((!filter_map.is_empty()).then(|| filters(&filter_map)))
+        .collect::<Vec<_>>();
+
+    Some(Route::List(if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path}?{}", query.join("&"))
+    }))
+}
+
+/// Maps a podman invocation onto the REST API, leaving the ones it can't answer to the CLI
+pub(crate) fn route(args: &[String]) -> Option<Route> {
+    let args = args.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        [kind @ ("container" | "network" | "volume" | "pod"), "exists", name] => Some(
+            Route::Exists(format!("/libpod/{kind}s/{}/exists", encode(name))),
+        ),
+        ["ps", args @ ..] => list("/libpod/containers/json", args),
+        ["pod", "ps", args @ ..] => list("/libpod/pods/json", args),
+        ["network", "ls", args @ ..] => list("/libpod/networks/json", args),
+        ["volume", "ls", args @ ..] => list("/libpod/volumes/json", args),
+        ["secret", "ls", args @ ..] => list("/libpod/secrets/json", args),
+        ["start", name] => Some(Route::Action(
+            format!("/libpod/containers/{}/start", encode(name)),
+            name.to_string(),
+        )),
+        ["stop", name] => Some(Route::Action(
+            format!("/libpod/containers/{}/stop", encode(name)),
+            name.to_string(),
+        )),
+        ["stop", "--time", timeout, name] => Some(Route::Action(
+            format!(
+                "/libpod/containers/{}/stop?timeout={}",
+                encode(name),
+                encode(timeout)
+            ),
+            name.to_string(),
+        )),
+        ["logs", flags @ .., name] => {
+            let mut query = vec![String::from("stdout=true"), String::from("stderr=true")];
+            let mut flags = flags.iter();
+
+            while let Some(flag) = flags.next() {
+                query.push(match *flag {
+                    "--follow" => String::from("follow=true"),
+                    "--timestamps" => String::from("timestamps=true"),
+                    "--since" | "--until" | "--tail" => {
+                        format!("{}={}", &flag[2..], encode(flags.next()?))
+                    }
+                    _ => return None,
+                });
+            }
+
+            Some(Route::Logs(
+                format!(
+                    "/libpod/containers/{}/logs?{}",
+                    encode(name),
+                    query.join("&")
+                ),
+                name.to_string(),
+            ))
+        }
+        ["events", "--format", "json", flags @ ..] => {
+            let mut query = vec![String::from("stream=true")];
+            let mut filter_map = IndexMap::<_, Vec<_>>::new();
+            let mut flags = flags.iter();
+
+            while let Some(flag) = flags.next() {
+                match *flag {
+                    "--since" | "--until" => {
+                        query.push(format!("{}={}", &flag[2..], encode(flags.next()?)));
+                    }
+                    "--filter" => {
+                        let (key, value) = flags.next()?.split_once('=')?;
+
+                        filter_map.entry(key).or_default().push(value);
+                    }
+                    _ => return None,
+                }
+            }
+
+            if !filter_map.is_empty() {
+                query.push(filters(&filter_map));
+            }
+
+            Some(Route::Events(format!("/libpod/events?{}", query.join("&"))))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Version {
+    version: semver::Version,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Inspect {
+    config: InspectConfig,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct InspectConfig {
+    #[serde(default)]
+    tty: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Error {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Event {
+    r#type: String,
+    action: String,
+    actor: Actor,
+    #[serde(rename = "time")]
+    time: i64,
+    #[serde(rename = "timeNano")]
+    time_nano: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Actor {
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(default)]
+    attributes: IndexMap<String, String>,
+}
+
+struct Response {
+    status: u16,
+    chunked: bool,
+    reader: BufReader<UnixStream>,
+}
+
+impl Response {
+    /// Reads the next piece of the body, or `None` at its end
+    async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.chunked {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).await?;
+
+            let size = usize::from_str_radix(line.trim().split(';').next().unwrap(), 16)?;
+
+            if size == 0 {
+                return Ok(None);
+            }
+
+            let mut chunk = vec![0; size + 2];
+            self.reader.read_exact(&mut chunk).await?;
+            chunk.truncate(size);
+
+            Ok(Some(chunk))
+        } else {
+            let mut chunk = vec![0; 8192];
+            let size = self.reader.read(&mut chunk).await?;
+            chunk.truncate(size);
+
+            Ok(Some(chunk).filter(|chunk| !chunk.is_empty()))
+        }
+    }
+
+    async fn text(mut self) -> Result<String> {
+        let mut body = Vec::new();
+
+        while let Some(chunk) = self.chunk().await? {
+            body.extend(chunk);
+        }
+
+        Ok(String::from_utf8_lossy(&body).to_string())
+    }
+
+    /// Fails with the message of the API if the request was not successful
+    async fn check(self) -> Result<Self> {
+        if self.status < 400 {
+            Ok(self)
+        } else {
+            let body = self.text().await?;
+
+            bail!(
+                "{}",
+                serde_json::from_str::<Error>(&body)
+                    .map(|error| error.message)
+                    .unwrap_or(body)
+            )
+        }
+    }
+
+    fn into_stream(self) -> impl Stream<Item = Result<Vec<u8>>> {
+        stream::try_unfold(self, |mut response| async move {
+            Ok(response.chunk().await?.map(|chunk| (chunk, response)))
+        })
+    }
+}
+
+/// Splits a byte stream into lines, keeping the last one even without a trailing newline
+fn lines<S>(chunks: S) -> impl Stream<Item = Result<String>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    chunks
+        .map_ok(Some)
+        .chain(stream::once(async { Ok(None) }))
+        .scan(Vec::new(), |buffer, chunk| {
+            let lines = match chunk {
+                Ok(Some(chunk)) => {
+                    buffer.extend(chunk);
+
+                    let end = buffer.iter().rposition(|byte| *byte == b'\n');
+                    let lines = end
+                        .map(|end| buffer.drain(..=end).collect::<Vec<_>>())
+                        .unwrap_or_default();
+
+                    lines
+                        .split(|byte| *byte == b'\n')
+                        .take(lines.iter().filter(|byte| **byte == b'\n').count())
+                        .map(|line| Ok(String::from_utf8_lossy(line).to_string()))
+                        .collect()
+                }
+                Ok(None) if !buffer.is_empty() => {
+                    vec![Ok(
+                        String::from_utf8_lossy(&std::mem::take(buffer)).to_string()
+                    )]
+                }
+                Ok(None) => Vec::new(),
+                Err(err) => vec![Err(err)],
+            };
+
+            async move { Some(stream::iter(lines)) }
+        })
+        .flatten()
+}
+
+/// Splits the frames of a multiplexed log stream into stdout and stderr lines
+fn demultiplex<S>(chunks: S) -> impl Stream<Item = Result<(OutputStream, String)>>
+where
+    S: Stream<Item = Result<Vec<u8>>>,
+{
+    chunks
+        .map_ok(Some)
+        .chain(stream::once(async { Ok(None) }))
+        .scan(
+            (Vec::new(), [Vec::new(), Vec::new()]),
+            |(buffer, partial_lines), chunk| {
+                let mut lines = Vec::new();
+
+                match chunk {
+                    Ok(Some(chunk)) => {
+                        buffer.extend(chunk);
+
+                        while buffer.len() >= 8 {
+                            let size =
+                                u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]])
+                                    as usize;
+
+                            if buffer.len() < 8 + size {
+                                break;
+                            }
+
+                            let frame = buffer.drain(..8 + size).collect::<Vec<_>>();
+                            let (i, stream) = if frame[0] == 2 {
+                                (1, OutputStream::Stderr)
+                            } else {
+                                (0, OutputStream::Stdout)
+                            };
+
+                            for byte in &frame[8..] {
+                                if *byte == b'\n' {
+                                    lines.push(Ok((
+                                        stream,
+                                        String::from_utf8_lossy(&partial_lines[i]).to_string(),
+                                    )));
+                                    partial_lines[i].clear();
+                                } else {
+                                    partial_lines[i].push(*byte);
+                                }
+                            }
+                        }
+                    }
+                    Ok(None) => {
+                        for (i, stream) in [OutputStream::Stdout, OutputStream::Stderr]
+                            .into_iter()
+                            .enumerate()
+                        {
+                            if !partial_lines[i].is_empty() {
+                                lines.push(Ok((
+                                    stream,
+                                    String::from_utf8_lossy(&partial_lines[i]).to_string(),
+                                )));
+                            }
+                        }
+                    }
+                    Err(err) => lines.push(Err(err)),
+                }
+
+                async move { Some(stream::iter(lines)) }
+            },
+        )
+        .flatten()
+}
+
+/// Rewrites a listing of the API in the schema of its `--format json` command, which only differs
+/// for containers, whose creation time the CLI gives as a Unix timestamp rather than in RFC 3339
+fn convert_listing(path: &str, body: &str) -> Result<String> {
+    if !path.starts_with("/libpod/containers/json") {
+        return Ok(body.to_string());
+    }
+
+    let mut containers = serde_json::from_str::<Vec<Value>>(body)?;
+
+    for container in &mut containers {
+        if let Some(created) = container["Created"].as_str() {
+            container["Created"] = Value::from(
+                parse_timestamp(created)?
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |created| created.as_secs()),
+            );
+        }
+    }
+
+    Ok(Value::from(containers).to_string())
+}
+
+/// Rewrites an event of the API in the schema of `podman events --format json`
+fn convert_event(line: &str) -> Result<String> {
+    let mut event = serde_json::from_str::<Event>(line)?;
+    let name = event.actor.attributes.shift_remove("name");
+    let image = event.actor.attributes.shift_remove("image");
+    let exit_code = event
+        .actor
+        .attributes
+        .shift_remove("containerExitCode")
+        .and_then(|exit_code| exit_code.parse::<i32>().ok());
+
+    Ok(json!({
+        "ID": event.actor.id,
+        "Name": name.unwrap_or_default(),
+        "Status": event.action,
+        "Type": event.r#type,
+        "Image": image,
+        "Time": event.time,
+        "timeNano": event.time_nano,
+        "ContainerExitCode": exit_code,
+        "Attributes": event.actor.attributes,
+    })
+    .to_string())
+}
+
+/// Client of the libpod REST API over a unix socket
+pub(crate) struct Client {
+    socket: PathBuf,
+}
+
+impl Client {
+    /// Finds the socket from the URL of the service, or the default rootless and rootful locations
+    pub(crate) fn socket(host: Option<&str>) -> Option<PathBuf> {
+        if let Some(host) = host {
+            return host.strip_prefix("unix://").map(PathBuf::from);
+        }
+
+        env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("podman/podman.sock"))
+            .into_iter()
+            .chain([PathBuf::from("/run/podman/podman.sock")])
+            .find(|socket| socket.exists())
+    }
+
+    /// Connects to the API, returning its version
+    pub(crate) async fn connect(socket: PathBuf) -> Result<(Self, semver::Version)> {
+        let client = Self { socket };
+        let version = serde_json::from_str::<Version>(
+            &client
+                .request("GET", "/libpod/version", None)
+                .await?
+                .text()
+                .await?,
+        )?
+        .version;
+
+        Ok((client, version))
+    }
+
+    async fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Response> {
+        let mut stream = UnixStream::connect(&self.socket).await?;
+        let body = body.map(Value::to_string).unwrap_or_default();
+
+        stream
+            .write_all(
+                format!(
+                    "{method} /{API_VERSION}{path} HTTP/1.1\r\nHost: d\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .await?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| anyhow!("Invalid response from the Podman API: {}", line.trim()))?;
+        let mut chunked = false;
+
+        loop {
+            line.clear();
+            reader.read_line(&mut line).await?;
+
+            let header = line.trim();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("transfer-encoding")
+                    && value.trim().eq_ignore_ascii_case("chunked")
+                {
+                    chunked = true;
+                }
+            }
+        }
+
+        Ok(Response {
+            status,
+            chunked,
+            reader,
+        })
+    }
+
+    pub(crate) async fn execute(&self, route: Route) -> Result<String> {
+        match route {
+            Route::Exists(path) => {
+                let response = self.request("GET", &path, None).await?;
+
+                match response.status {
+                    204 => Ok(String::new()),
+                    404 => bail!("{path} does not exist"),
+                    _ => response.check().await.map(|_| String::new()),
+                }
+            }
+            Route::List(path) => {
+                let body = self
+                    .request("GET", &path, None)
+                    .await?
+                    .check()
+                    .await?
+                    .text()
+                    .await?;
+
+                convert_listing(&path, &body)
+            }
+            Route::Action(path, name) => {
+                self.request("POST", &path, None).await?.check().await?;
+
+                Ok(format!("{name}\n"))
+            }
+            Route::Logs(..) | Route::Events(_) => bail!("Streams can only be watched"),
+        }
+    }
+
+    /// Pulls an image under a pull policy, failing with the error reported in the progress stream
+    pub(crate) async fn pull(&self, image: &str, policy: &str) -> Result<()> {
+        let progress = self
+            .request(
+                "POST",
+                &format!(
+                    "/libpod/images/pull?reference={}&policy={}",
+                    encode(image),
+                    encode(policy)
+                ),
+                None,
+            )
+            .await?
+            .check()
+            .await?
+            .text()
+            .await?;
+
+        if let Some(error) = progress
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find_map(|line| line["error"].as_str().map(ToString::to_string))
+        {
+            bail!("{error}");
+        }
+
+        Ok(())
+    }
+
+    /// Creates a resource from its payload, printing its ID or name like the CLI does
+    pub(crate) async fn create(&self, payload: Payload) -> Result<String> {
+        let (path, body) = match payload {
+            Payload::Container(body) => ("/libpod/containers/create", body),
+            Payload::Pod(body) => ("/libpod/pods/create", body),
+            Payload::Network(body) => ("/libpod/networks/create", body),
+            Payload::Volume(body) => ("/libpod/volumes/create", body),
+        };
+        let response = serde_json::from_str::<Value>(
+            &self
+                .request("POST", path, Some(&body))
+                .await?
+                .check()
+                .await?
+                .text()
+                .await?,
+        )?;
+
+        Ok(format!(
+            "{}\n",
+            ["Id", "name", "Name"]
+                .into_iter()
+                .find_map(|key| response[key].as_str())
+                .unwrap_or_default()
+        ))
+    }
+
+    pub(crate) fn watch(
+        &self,
+        route: Route,
+    ) -> impl Stream<Item = Result<(OutputStream, String)>> + 'static {
+        let socket = self.socket.clone();
+
+        stream::once(async move {
+            let client = Client { socket };
+
+            match route {
+                Route::Logs(path, name) => {
+                    let inspect = serde_json::from_str::<Inspect>(
+                        &client
+                            .request(
+                                "GET",
+                                &format!("/libpod/containers/{}/json", encode(&name)),
+                                None,
+                            )
+                            .await?
+                            .check()
+                            .await?
+                            .text()
+                            .await?,
+                    )?;
+                    let response = client.request("GET", &path, None).await?.check().await?;
+
+                    // The output of a TTY is sent raw, with its lines ending in CRLF, as it doesn't tell stdout
+                    // from stderr
+                    if inspect.config.tty {
+                        Ok(lines(response.into_stream())
+                            .map_ok(|line| {
+                                (
+                                    OutputStream::Stdout,
+                                    line.strip_suffix('\r').unwrap_or(&line).to_string(),
+                                )
+                            })
+                            .boxed())
+                    } else {
+                        Ok(demultiplex(response.into_stream()).boxed())
+                    }
+                }
+                Route::Events(path) => {
+                    let response = client.request("GET", &path, None).await?.check().await?;
+
+                    Ok(lines(response.into_stream())
+                        .try_filter(|line| futures::future::ready(!line.trim().is_empty()))
+                        .and_then(|line| async move {
+                            Ok((OutputStream::Stdout, convert_event(&line)?))
+                        })
+                        .boxed())
+                }
+                _ => bail!("Only logs and events can be watched"),
+            }
+        })
+        .try_flatten()
+    }
+}
+
+/// Serves the invocations the REST API can answer, and the others through the CLI
+pub(crate) struct Api {
+    client: Client,
+    cli: Cli,
+}
+
+impl Api {
+    pub(crate) fn new(client: Client, cli: Cli) -> Self {
+        Self { client, cli }
+    }
+}
+
+fn strings(args: &[OsString]) -> Vec<String> {
+    args.iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+#[async_trait(?Send)]
+impl Executor for Api {
+    async fn run(&self, args: Vec<OsString>) -> Result<String> {
+        let args_strings = strings(&args);
+
+        match route(&args_strings).filter(|route| !route.is_stream()) {
+            Some(route) => self.client.execute(route).await.with_context(|| {
+                anyhow!(
+                    "`podman {}` returned an error",
+                    shell_words::join(&args_strings)
+                )
+            }),
+            None => self.cli.run(args).await,
+        }
+    }
+
+    async fn create(&self, args: Vec<OsString>, payload: Payload) -> Result<String> {
+        let args = strings(&args);
+
+        // Unlike the CLI, the API doesn't pull the image of a container it creates
+        async {
+            if let Payload::Container(body) = &payload {
+                if let Some(image) = body["image"].as_str() {
+                    let policy = args
+                        .windows(2)
+                        .find(|pair| pair[0] == "--pull")
+                        .map_or("missing", |pair| &pair[1]);
+
+                    self.client.pull(image, policy).await?;
+                }
+            }
+
+            self.client.create(payload).await
+        }
+        .await
+        .with_context(|| anyhow!("`podman {}` returned an error", shell_words::join(&args)))
+    }
+
+    async fn feed(&self, args: Vec<OsString>, input: &str) -> Result<String> {
+        self.cli.feed(args, input).await
+    }
+
+    fn watch(&self, args: Vec<OsString>) -> Result<Lines> {
+        match route(&strings(&args)).filter(Route::is_stream) {
+            Some(route) => Ok(self.client.watch(route).boxed()),
+            None => self.cli.watch(args),
+        }
+    }
+
+    async fn pipe(&self, source: Vec<OsString>, destination: Vec<OsString>) -> Result<()> {
+        self.cli.pipe(source, destination).await
+    }
+
+    async fn attach(&self, args: Vec<OsString>) -> Result<()> {
+        self.cli.attach(args).await
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.cli.create_dir(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    use pretty_assertions::assert_eq;
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    /// Serves canned responses, chunking bodies sent as several parts, and records the requests
+    /// by path and body
+    fn serve(
+        socket: &Path,
+        responses: Vec<(&'static str, u16, Vec<Vec<u8>>)>,
+    ) -> Arc<Mutex<Vec<(String, String)>>> {
+        let listener = UnixListener::bind(socket).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+
+                let mut line = String::new();
+                let mut length = 0;
+
+                while reader.read_line(&mut line).await.unwrap() > 2 {
+                    if let Some(value) = line.trim().strip_prefix("Content-Length: ") {
+                        length = value.parse().unwrap();
+                    }
+
+                    line.clear();
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+
+                let path = request.split_whitespace().nth(1).unwrap().to_string();
+
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((path.clone(), String::from_utf8(body).unwrap()));
+
+                let (_, status, parts) = responses
+                    .iter()
+                    .find(|(prefix, _, _)| path.starts_with(&format!("/{API_VERSION}{prefix}")))
+                    .cloned()
+                    .unwrap_or(("", 404, vec![br#"{"message":"not found"}"#.to_vec()]));
+                let mut response =
+                    format!("HTTP/1.1 {status} OK\r\nTransfer-Encoding: chunked\r\n\r\n")
+                        .into_bytes();
+
+                for part in parts {
+                    response.extend(format!("{:x}\r\n", part.len()).into_bytes());
+                    response.extend(part);
+                    response.extend(b"\r\n");
+                }
+
+                response.extend(b"0\r\n\r\n");
+                reader.get_mut().write_all(&response).await.unwrap();
+            }
+        });
+
+        requests
+    }
+
+    fn socket() -> PathBuf {
+        env::temp_dir().join(format!("haddock-api-{}.sock", fastrand::u64(..)))
+    }
+
+    fn frame(stream: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend((payload.len() as u32).to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    #[test]
+    fn routes() {
+        let route = |args: &str| route(&shell_words::split(args).unwrap());
+
+        assert_eq!(
+            route("ps --all --format json --filter label=io.podman.compose.project=foo"),
+            Some(Route::List(String::from("/libpod/containers/json?all=true&filters=%7B%22label%22%3A%5B%22io.podman.compose.project%3Dfoo%22%5D%7D")))
+        );
+        assert_eq!(
+            route("network exists foo_default"),
+            Some(Route::Exists(String::from(
+                "/libpod/networks/foo_default/exists"
+            )))
+        );
+        assert_eq!(
+            route("stop --time 3 foo_bar_1"),
+            Some(Route::Action(
+                String::from("/libpod/containers/foo_bar_1/stop?timeout=3"),
+                String::from("foo_bar_1")
+            ))
+        );
+        assert_eq!(
+            route("logs --follow --tail 5 foo_bar_1"),
+            Some(Route::Logs(
+                String::from(
+                    "/libpod/containers/foo_bar_1/logs?stdout=true&stderr=true&follow=true&tail=5"
+                ),
+                String::from("foo_bar_1")
+            ))
+        );
+        assert_eq!(
+            route("events --format json --filter type=container --filter label=io.podman.compose.project=foo --since 5m"),
+            Some(Route::Events(String::from("/libpod/events?stream=true&since=5m&filters=%7B%22type%22%3A%5B%22container%22%5D%2C%22label%22%3A%5B%22io.podman.compose.project%3Dfoo%22%5D%7D")))
+        );
+        assert_eq!(route("ps --format {{.Names}}"), None);
+        assert_eq!(route("create --name foo alpine"), None);
+    }
+
+    #[tokio::test]
+    async fn execute() {
+        let socket = socket();
+
+        serve(
+            &socket,
+            vec![
+                (
+                    "/libpod/version",
+                    200,
+                    vec![br#"{"Version":"4.5.0"}"#.to_vec()],
+                ),
+                ("/libpod/containers/foo_bar_1/exists", 204, vec![]),
+                (
+                    "/libpod/containers/json",
+                    200,
+                    vec![b"[".to_vec(), b"]".to_vec()],
+                ),
+                ("/libpod/containers/foo_bar_1/start", 204, vec![]),
+            ],
+        );
+
+        let (client, version) = Client::connect(socket.clone()).await.unwrap();
+
+        assert_eq!(version, semver::Version::new(4, 5, 0));
+        assert!(client
+            .execute(
+                route(&[
+                    String::from("container"),
+                    String::from("exists"),
+                    String::from("foo_bar_1")
+                ])
+                .unwrap()
+            )
+            .await
+            .is_ok());
+        assert!(client
+            .execute(
+                route(&[
+                    String::from("container"),
+                    String::from("exists"),
+                    String::from("foo_baz_1")
+                ])
+                .unwrap()
+            )
+            .await
+            .is_err());
+        assert_eq!(
+            client
+                .execute(Route::List(String::from(
+                    "/libpod/containers/json?all=true"
+                )))
+                .await
+                .unwrap(),
+            "[]"
+        );
+        assert_eq!(
+            client
+                .execute(Route::Action(
+                    String::from("/libpod/containers/foo_bar_1/start"),
+                    String::from("foo_bar_1")
+                ))
+                .await
+                .unwrap(),
+            "foo_bar_1\n"
+        );
+
+        std::fs::remove_file(socket).ok();
+    }
+
+    #[tokio::test]
+    async fn listings() {
+        let socket = socket();
+
+        serve(
+            &socket,
+            vec![
+                (
+                    "/libpod/containers/json",
+                    200,
+                    vec![br#"[{"AutoRemove":false,"Command":["nginx","-g","daemon off;"],"Created":"2023-04-01T08:00:00.5+08:00","CreatedAt":"","Exited":false,"ExitedAt":-62135596800,"ExitCode":0,"Id":"3c5a","Image":"docker.io/library/nginx:latest","ImageID":"6efc","IsInfra":false,"Labels":{"io.podman.compose.project":"foo","io.podman.compose.service":"web","io.podman.compose.container-number":"1","io.podman.compose.oneoff":"false"},"Mounts":[],"Names":["foo_web_1"],"Namespaces":{},"Networks":["foo_default"],"Pid":1234,"Pod":"abcd","PodName":"foo","Ports":[{"host_ip":"","container_port":80,"host_port":8080,"range":1,"protocol":"tcp"}],"Restarts":0,"Size":null,"StartedAt":1680307201,"State":"running","Status":""}]"#.to_vec()],
+                ),
+                (
+                    "/libpod/pods/json",
+                    200,
+                    vec![br#"[{"Cgroup":"user.slice","Containers":[{"Id":"3c5a","Names":"foo_web_1","Status":"running","RestartCount":0}],"Created":"2023-04-01T00:00:00.5Z","Id":"abcd","InfraId":"ef01","Name":"foo","Namespace":"","Networks":["foo_default"],"Status":"Running","Labels":{"io.podman.compose.project":"foo","io.podman.compose.project.working-dir":"/srv/foo"}}]"#.to_vec()],
+                ),
+                (
+                    "/libpod/networks/json",
+                    200,
+                    vec![br#"[{"name":"foo_default","id":"9f2e","driver":"bridge","network_interface":"podman1","created":"2023-04-01T00:00:00.5Z","subnets":[{"subnet":"10.89.0.0/24","gateway":"10.89.0.1"}],"ipv6_enabled":false,"internal":false,"dns_enabled":true,"labels":{"io.podman.compose.project":"foo"},"ipam_options":{"driver":"host-local"}}]"#.to_vec()],
+                ),
+                (
+                    "/libpod/volumes/json",
+                    200,
+                    vec![br#"[{"Name":"foo_data","Driver":"local","Mountpoint":"/srv/volumes/foo_data/_data","CreatedAt":"2023-04-01T00:00:00.5Z","Labels":{"io.podman.compose.project":"foo"},"Scope":"local","Options":{},"MountCount":0,"NeedsCopyUp":true,"NeedsChown":true}]"#.to_vec()],
+                ),
+            ],
+        );
+
+        let client = Client {
+            socket: socket.clone(),
+        };
+        let list = |args: &'static str| {
+            let client = &client;
+
+            async move {
+                client
+                    .execute(route(&shell_words::split(args).unwrap()).unwrap())
+                    .await
+                    .unwrap()
+            }
+        };
+        let containers = serde_json::from_str::<Vec<crate::podman::types::Container>>(
+            &list("ps --all --format json").await,
+        )
+        .unwrap();
+        let pods = serde_json::from_str::<Vec<crate::podman::types::Pod>>(
+            &list("pod ps --format json").await,
+        )
+        .unwrap();
+        let networks = serde_json::from_str::<Vec<crate::podman::types::Network>>(
+            &list("network ls --format json").await,
+        )
+        .unwrap();
+        let volumes = serde_json::from_str::<Vec<crate::podman::types::Volume>>(
+            &list("volume ls --format json").await,
+        )
+        .unwrap();
+
+        assert_eq!(containers[0].created, 1_680_307_200);
+        assert_eq!(containers[0].started_at, 1_680_307_201);
+        assert_eq!(containers[0].names, ["foo_web_1"]);
+        assert_eq!(
+            containers[0]
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.service.as_deref()),
+            Some("web")
+        );
+        assert_eq!(pods[0].name, "foo");
+        assert_eq!(pods[0].containers[0].status, "running");
+        assert_eq!(
+            pods[0]
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.working_dir.as_deref()),
+            Some("/srv/foo")
+        );
+        assert_eq!(networks[0].name, "foo_default");
+        assert_eq!(volumes[0].name, "foo_data");
+
+        std::fs::remove_file(socket).ok();
+    }
+
+    #[tokio::test]
+    async fn create() {
+        let socket = socket();
+        let requests = serve(
+            &socket,
+            vec![
+                (
+                    "/libpod/pods/create",
+                    201,
+                    vec![br#"{"Id":"abc"}"#.to_vec()],
+                ),
+                (
+                    "/libpod/volumes/create",
+                    201,
+                    vec![br#"{"Name":"foo_data","Driver":"local"}"#.to_vec()],
+                ),
+                (
+                    "/libpod/networks/create",
+                    409,
+                    vec![br#"{"message":"network name foo_default already used"}"#.to_vec()],
+                ),
+            ],
+        );
+        let client = Client {
+            socket: socket.clone(),
+        };
+
+        assert_eq!(
+            client
+                .create(Payload::Pod(json!({ "name": "foo" })))
+                .await
+                .unwrap(),
+            "abc\n"
+        );
+        assert_eq!(
+            client
+                .create(Payload::Volume(json!({ "Name": "foo_data" })))
+                .await
+                .unwrap(),
+            "foo_data\n"
+        );
+        assert_eq!(
+            client
+                .create(Payload::Network(json!({ "name": "foo_default" })))
+                .await
+                .unwrap_err()
+                .to_string(),
+            "network name foo_default already used"
+        );
+        assert_eq!(
+            requests.lock().unwrap()[..2],
+            [
+                (
+                    format!("/{API_VERSION}/libpod/pods/create"),
+                    String::from(r#"{"name":"foo"}"#)
+                ),
+                (
+                    format!("/{API_VERSION}/libpod/volumes/create"),
+                    String::from(r#"{"Name":"foo_data"}"#)
+                ),
+            ]
+        );
+
+        std::fs::remove_file(socket).ok();
+    }
+
+    #[tokio::test]
+    async fn create_container() {
+        let socket = socket();
+        let requests = serve(
+            &socket,
+            vec![
+                (
+                    "/libpod/images/pull?reference=alpine",
+                    200,
+                    vec![
+                        br#"{"stream":"Trying to pull docker.io/library/alpine:latest..."}"#
+                            .to_vec(),
+                        b"\n".to_vec(),
+                        br#"{"images":["abc"],"id":"abc"}"#.to_vec(),
+                    ],
+                ),
+                (
+                    "/libpod/images/pull?reference=missing",
+                    200,
+                    vec![br#"{"error":"missing: image not known"}"#.to_vec()],
+                ),
+                (
+                    "/libpod/containers/create",
+                    201,
+                    vec![br#"{"Id":"def","Warnings":[]}"#.to_vec()],
+                ),
+            ],
+        );
+        let client = Client {
+            socket: socket.clone(),
+        };
+
+        client.pull("alpine", "newer").await.unwrap();
+
+        assert_eq!(
+            client
+                .pull("missing", "never")
+                .await
+                .unwrap_err()
+                .to_string(),
+            "missing: image not known"
+        );
+        assert_eq!(
+            client
+                .create(Payload::Container(
+                    json!({ "name": "foo_bar_1", "image": "alpine" })
+                ))
+                .await
+                .unwrap(),
+            "def\n"
+        );
+        assert_eq!(
+            requests.lock().unwrap()[0].0,
+            format!("/{API_VERSION}/libpod/images/pull?reference=alpine&policy=newer")
+        );
+
+        std::fs::remove_file(socket).ok();
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let socket = socket();
+        let mut logs = frame(1, b"one\ntw");
+        logs.extend(frame(2, b"oops\n"));
+
+        serve(
+            &socket,
+            vec![
+                (
+                    "/libpod/containers/foo_bar_1/json",
+                    200,
+                    vec![br#"{"Config":{"Tty":false}}"#.to_vec()],
+                ),
+                (
+                    "/libpod/containers/foo_bar_1/logs",
+                    200,
+                    vec![logs[..5].to_vec(), logs[5..].to_vec(), frame(1, b"o")],
+                ),
+                (
+                    "/libpod/containers/foo_tty_1/json",
+                    200,
+                    vec![br#"{"Config":{"Tty":true}}"#.to_vec()],
+                ),
+                (
+                    "/libpod/containers/foo_tty_1/logs",
+                    200,
+                    vec![b"one\r\ntw".to_vec(), b"o".to_vec()],
+                ),
+                (
+                    "/libpod/events",
+                    200,
+                    vec![
+                        br#"{"Type":"container","Action":"start","Actor":{"ID":"abc","Attributes":{"name":"foo_bar_1","image":"alpine","io.podman.compose.service":"bar"}},"time":1,"#.to_vec(),
+                        b"\"timeNano\":1000000000}\n".to_vec(),
+                    ],
+                ),
+            ],
+        );
+
+        let client = Client {
+            socket: socket.clone(),
+        };
+
+        assert_eq!(
+            client
+                .watch(Route::Logs(
+                    String::from("/libpod/containers/foo_bar_1/logs?stdout=true"),
+                    String::from("foo_bar_1")
+                ))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap(),
+            vec![
+                (OutputStream::Stdout, String::from("one")),
+                (OutputStream::Stderr, String::from("oops")),
+                (OutputStream::Stdout, String::from("two")),
+            ]
+        );
+        assert_eq!(
+            client
+                .watch(Route::Logs(
+                    String::from("/libpod/containers/foo_tty_1/logs?stdout=true"),
+                    String::from("foo_tty_1")
+                ))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap(),
+            vec![
+                (OutputStream::Stdout, String::from("one")),
+                (OutputStream::Stdout, String::from("two")),
+            ]
+        );
+
+        let events = client
+            .watch(Route::Events(String::from("/libpod/events?stream=true")))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let event = serde_json::from_str::<crate::podman::types::Event>(&events[0].1).unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(event.name, "foo_bar_1");
+        assert_eq!(event.status, "start");
+        assert_eq!(event.labels().service.as_deref(), Some("bar"));
+
+        std::fs::remove_file(socket).ok();
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Output, Stdio},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Error, Result};
+use async_trait::async_trait;
+use futures::{stream::select, try_join, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+use tokio_stream::wrappers::LinesStream;
+
+use super::{
+    collect,
+    types::{OutputStream, Version},
+    Executor, Lines,
+};
+use crate::{compose::STYLED_WARNING, config::Config};
+
+/// Identity of the podman binary, whose changes invalidate its cached version
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+struct Binary {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+impl Binary {
+    /// Resolves `program` like a shell would, searching `PATH` unless it is a path itself
+    fn find(program: &Path) -> Option<Self> {
+        let path = if program.components().count() > 1 {
+            program.to_path_buf()
+        } else {
+            env::split_paths(&env::var_os("PATH")?)
+                .map(|directory| directory.join(program))
+                .find(|path| path.is_file())?
+        };
+        let path = fs::canonicalize(path).ok()?;
+        let metadata = fs::metadata(&path).ok()?;
+
+        Some(Self {
+            path,
+            len: metadata.len(),
+            modified: metadata.modified().ok()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedVersion {
+    binary: Binary,
+    version: semver::Version,
+}
+
+fn cache_file() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|directory| directory.join("haddock").join("podman-version.json"))
+}
+
+/// Runs podman invocations by spawning the podman binary, possibly as a client of a remote service
+pub(crate) struct Cli {
+    program: OsString,
+    global_args: Vec<OsString>,
+    remote: bool,
+    project_directory: PathBuf,
+}
+
+impl Cli {
+    pub(crate) fn new(config: &Config) -> Self {
+        let mut global_args = config.podman();
+        let program = global_args.remove(0);
+
+        Self {
+            program,
+            global_args,
+            remote: config.remote(),
+            project_directory: config.project_directory.clone(),
+        }
+    }
+
+    /// Version of podman, cached between invocations for as long as its binary is unchanged
+    pub(crate) async fn version(&self) -> Result<semver::Version> {
+        let binary = Binary::find(Path::new(&self.program));
+        let cache = cache_file();
+
+        if let (Some(binary), Some(cache)) = (&binary, &cache) {
+            if let Some(cached) = fs::read(cache)
+                .ok()
+                .and_then(|cached| serde_json::from_slice::<CachedVersion>(&cached).ok())
+                .filter(|cached| cached.binary == *binary)
+            {
+                return Ok(cached.version);
+            }
+        }
+
+        let output = self.run(collect(["version", "--format", "json"])).await?;
+        let version = serde_json::from_str::<Version>(&output)
+            .with_context(|| anyhow!("Podman version not recognised"))?
+            .client
+            .version;
+
+        // Failing to write the cache only costs the next invocation a check
+        if let (Some(binary), Some(cache)) = (binary, cache) {
+            if let Some(directory) = cache.parent() {
+                fs::create_dir_all(directory).ok();
+            }
+
+            fs::write(
+                &cache,
+                serde_json::to_vec(&CachedVersion {
+                    binary,
+                    version: version.clone(),
+                })?,
+            )
+            .ok();
+        }
+
+        Ok(version)
+    }
+
+    fn command(&self, args: Vec<OsString>) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .current_dir(&self.project_directory)
+            .args(&self.global_args)
+            .args(args);
+
+        command
+    }
+}
+
+fn display(command: &Command) -> String {
+    format!(
+        "{} {}",
+        command.as_std().get_program().to_string_lossy(),
+        shell_words::join(
+            command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+        )
+    )
+}
+
+fn check(command: &Command, output: Output) -> Result<String> {
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(anyhow!("{}", String::from_utf8_lossy(&output.stderr))
+            .context(anyhow!("`{}` returned an error", display(command))))
+    }
+}
+
+#[async_trait(?Send)]
+impl Executor for Cli {
+    async fn run(&self, args: Vec<OsString>) -> Result<String> {
+        let mut command = self.command(args);
+        let output = command
+            .output()
+            .await
+            .with_context(|| anyhow!("`{}` cannot be executed", display(&command)))?;
+
+        check(&command, output)
+    }
+
+    async fn feed(&self, args: Vec<OsString>, input: &str) -> Result<String> {
+        let mut command = self.command(args);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+
+        stdin.write_all(input.as_bytes()).await?;
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+
+        check(&command, output)
+    }
+
+    fn watch(&self, args: Vec<OsString>) -> Result<Lines> {
+        let child = self
+            .command(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = BufReader::new(child.stdout.unwrap()).lines();
+        let stderr = BufReader::new(child.stderr.unwrap()).lines();
+
+        Ok(select(
+            LinesStream::new(stdout).map_ok(|line| (OutputStream::Stdout, line)),
+            LinesStream::new(stderr).map_ok(|line| (OutputStream::Stderr, line)),
+        )
+        .map_err(Error::from)
+        .boxed())
+    }
+
+    async fn pipe(&self, source: Vec<OsString>, destination: Vec<OsString>) -> Result<()> {
+        let mut source = self.command(source);
+        let mut destination = self.command(destination);
+
+        let mut source_child = source
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout: Stdio = source_child.stdout.take().unwrap().try_into()?;
+        let destination_child = destination
+            .stdin(stdout)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (source_output, destination_output) = try_join!(
+            source_child.wait_with_output(),
+            destination_child.wait_with_output()
+        )?;
+
+        check(&source, source_output)?;
+        check(&destination, destination_output)?;
+
+        Ok(())
+    }
+
+    async fn attach(&self, args: Vec<OsString>) -> Result<()> {
+        let status = self.command(args).spawn()?.wait().await?;
+
+        if !status.success() {
+            process::exit(status.code().unwrap_or(1));
+        }
+
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        // The directory would be created here rather than on the host of the containers
+        if self.remote {
+            eprintln!(
+                "{} Podman is remote, not creating bind mount source {}",
+                *STYLED_WARNING,
+                path.display()
+            );
+
+            return Ok(());
+        }
+
+        Ok(fs::create_dir_all(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn version_cache() {
+        let directory = env::temp_dir().join(format!("haddock-version-{}", fastrand::u64(..)));
+        let bin = directory.join("bin");
+        let podman = bin.join("podman");
+
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(
+            &podman,
+            "#!/bin/sh\necho '{\"Client\":{\"Version\":\"4.5.1\"}}'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&podman, fs::Permissions::from_mode(0o755)).unwrap();
+
+        temp_env::with_vars(
+            [
+                ("PATH", Some(bin.as_os_str())),
+                ("XDG_CACHE_HOME", Some(directory.as_os_str())),
+            ],
+            || {
+                let runtime = tokio::runtime::Runtime::new().unwrap();
+                let cli = Cli::new(&Config {
+                    project_directory: directory.clone(),
+                    ..Config::default()
+                });
+
+                assert_eq!(
+                    runtime.block_on(cli.version()).unwrap(),
+                    semver::Version::new(4, 5, 1)
+                );
+
+                // The binary can no longer run, but it is unchanged so it isn't asked again
+                fs::set_permissions(&podman, fs::Permissions::from_mode(0o644)).unwrap();
+
+                assert_eq!(
+                    runtime.block_on(cli.version()).unwrap(),
+                    semver::Version::new(4, 5, 1)
+                );
+
+                // Replacing it invalidates the cache
+                fs::write(&podman, "#!/bin/sh\nexit 1\n").unwrap();
+
+                assert!(runtime.block_on(cli.version()).is_err());
+            },
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn remote() {
+        let directory = env::temp_dir().join(format!("haddock-remote-{}", fastrand::u64(..)));
+        let podman = directory.join("podman-remote");
+
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(&podman, "#!/bin/sh\necho \"$@\"\n").unwrap();
+        fs::set_permissions(&podman, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cli = Cli::new(&Config {
+            project_directory: directory.clone(),
+            podman_path: Some(podman),
+            url: Some(String::from(
+                "ssh://core@localhost:2222/run/podman/podman.sock",
+            )),
+            identity: Some(PathBuf::from("/home/core/.ssh/id_ed25519")),
+            ..Config::default()
+        });
+        let output = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(cli.run(collect(["ps", "--all"])))
+            .unwrap();
+
+        assert_eq!(
+            output,
+            "--url ssh://core@localhost:2222/run/podman/podman.sock \
+             --identity /home/core/.ssh/id_ed25519 ps --all\n"
+        );
+
+        // Bind mount sources would be created on this machine instead of the remote one
+        let source = directory.join("data");
+
+        cli.create_dir(&source).unwrap();
+
+        assert!(!source.exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+#![cfg(test)]
+
+use std::{
+    cell::{Cell, RefCell},
+    ffi::OsString,
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+
+use super::{join, Executor, Lines};
+
+/// Records the invocations it receives, answering them from a script of canned outputs
+#[derive(Default)]
+pub(crate) struct Fake {
+    script: Vec<(Vec<String>, bool, Result<String, String>)>,
+    invocations: Rc<RefCell<Vec<(usize, String)>>>,
+    delay: Option<Duration>,
+    in_flight: Cell<usize>,
+    peak: Rc<Cell<usize>>,
+    /// Number of the current batch, a run of invocations of which some were always in flight
+    batch: Cell<usize>,
+}
+
+impl Fake {
+    /// Answers the invocations starting with `prefix`, earlier entries taking precedence
+    pub(crate) fn script(mut self, prefix: &[&str], output: Result<&str, &str>) -> Self {
+        self.script.push((
+            prefix.iter().map(ToString::to_string).collect(),
+            false,
+            output.map(String::from).map_err(String::from),
+        ));
+        self
+    }
+
+    /// Answers the invocations of exactly `args`, earlier entries taking precedence
+    pub(crate) fn script_exact(mut self, args: &[&str], output: Result<&str, &str>) -> Self {
+        self.script.push((
+            args.iter().map(ToString::to_string).collect(),
+            true,
+            output.map(String::from).map_err(String::from),
+        ));
+        self
+    }
+
+    /// Makes each invocation stay in flight for `delay` rather than a single yield
+    pub(crate) fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Handle on the highest number of invocations seen in flight at once
+    pub(crate) fn peak(&self) -> Rc<Cell<usize>> {
+        self.peak.clone()
+    }
+
+    /// Handle on the recorded invocations, tagged with their batch, which outlives the fake once it
+    /// is boxed
+    pub(crate) fn invocations(&self) -> Rc<RefCell<Vec<(usize, String)>>> {
+        self.invocations.clone()
+    }
+
+    fn answer(&self, args: &[OsString]) -> Result<String> {
+        let args = args
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        self.script
+            .iter()
+            .find(|(prefix, exact, _)| {
+                if *exact {
+                    args == *prefix
+                } else {
+                    args.starts_with(prefix)
+                }
+            })
+            .map(|(_, _, output)| output.clone().map_err(|err| anyhow!(err)))
+            .unwrap_or_else(|| Ok(String::new()))
+    }
+
+    /// Records an invocation, in a new batch unless others are still in flight
+    fn record(&self, invocation: String) {
+        if self.in_flight.get() == 0 {
+            self.batch.set(self.batch.get() + 1);
+        }
+
+        self.invocations
+            .borrow_mut()
+            .push((self.batch.get(), invocation));
+    }
+
+    /// Keeps an invocation in flight for a while, so that the concurrent ones start meanwhile
+    async fn fly(&self) {
+        self.in_flight.set(self.in_flight.get() + 1);
+        self.peak.set(self.peak.get().max(self.in_flight.get()));
+
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        } else {
+            tokio::task::yield_now().await;
+        }
+
+        self.in_flight.set(self.in_flight.get() - 1);
+    }
+}
+
+#[async_trait(?Send)]
+impl Executor for Fake {
+    async fn run(&self, args: Vec<OsString>) -> Result<String> {
+        self.record(format!("podman {}", join(&args)));
+        self.fly().await;
+        self.answer(&args)
+    }
+
+    async fn feed(&self, args: Vec<OsString>, input: &str) -> Result<String> {
+        self.record(format!(
+            "podman {}\n{}",
+            join(&args),
+            input
+                .lines()
+                .map(|line| format!("    {line}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ));
+        self.fly().await;
+        self.answer(&args)
+    }
+
+    fn watch(&self, args: Vec<OsString>) -> Result<Lines> {
+        self.record(format!("podman {}", join(&args)));
+        self.answer(&args)?;
+
+        Ok(stream::empty().boxed())
+    }
+
+    async fn pipe(&self, source: Vec<OsString>, destination: Vec<OsString>) -> Result<()> {
+        self.record(format!(
+            "podman {} | podman {}",
+            join(&source),
+            join(&destination)
+        ));
+        self.fly().await;
+        self.answer(&source)?;
+        self.answer(&destination).map(|_| ())
+    }
+
+    async fn attach(&self, args: Vec<OsString>) -> Result<()> {
+        self.record(format!("podman {}", join(&args)));
+        self.fly().await;
+        self.answer(&args).map(|_| ())
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.record(format!(
+            "mkdir -p {}",
+            shell_words::quote(&path.to_string_lossy())
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,525 @@
+use std::{
+    cell::RefCell,
+    fmt::{self, Display, Formatter, Write},
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use heck::{AsKebabCase, AsShoutySnakeCase};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use serde_yaml::Value;
+
+/// Output of a dry run
+#[derive(ValueEnum, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PlanFormat {
+    /// Podman commands, printed as they would be executed
+    #[default]
+    Text,
+    /// POSIX shell script of the mutations, in dependency order
+    Script,
+    /// Planned actions per resource, with their reason
+    Json,
+}
+
+#[derive(Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Action {
+    Create,
+    Start,
+    Stop,
+    Restart,
+    Kill,
+    Pause,
+    Unpause,
+    Remove,
+    Keep,
+    Run,
+    Copy,
+    Play,
+    Prune,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", AsKebabCase(format!("{self:?}")))
+    }
+}
+
+#[derive(Serialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Resource {
+    Container,
+    Pod,
+    Network,
+    Volume,
+    Secret,
+    Image,
+    Kube,
+    Directory,
+}
+
+impl Display for Resource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", AsKebabCase(format!("{self:?}")))
+    }
+}
+
+/// Action planned on a resource, along with the command carrying it out
+#[skip_serializing_none]
+#[derive(Serialize, Debug)]
+pub(crate) struct Step {
+    pub(crate) action: Action,
+    pub(crate) resource: Resource,
+    pub(crate) name: Option<String>,
+    pub(crate) reason: String,
+    pub(crate) command: Option<String>,
+    pub(crate) input: Option<String>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub(crate) variables: IndexMap<String, String>,
+}
+
+/// Classifies a podman invocation, returning `None` for those that only read state
+pub(crate) fn classify(args: &[String]) -> Option<(Action, Resource, Option<String>)> {
+    let mut args = args;
+
+    // Global options of a service, such as `--runtime`, precede the subcommand
+    while let [option, _, rest @ ..] = args {
+        if !option.starts_with("--") || option.contains('=') {
+            break;
+        }
+
+        args = rest;
+    }
+
+    let last = args.last().cloned();
+    let container = |args: &[String]| {
+        args.iter()
+            .find_map(|arg| arg.split_once(':').map(|(name, _)| name.to_string()))
+    };
+
+    match args {
+        [command, rest @ ..] if command == "create" || command == "run" => Some((
+            if command == "create" {
+                Action::Create
+            } else {
+                Action::Run
+            },
+            Resource::Container,
+            rest.windows(2)
+                .find(|pair| pair[0] == "--name")
+                .map(|pair| pair[1].clone()),
+        )),
+        [resource, command, rest @ ..]
+            if ["pod", "network", "volume", "secret", "image"].contains(&resource.as_str()) =>
+        {
+            let resource = match resource.as_str() {
+                "pod" => Resource::Pod,
+                "network" => Resource::Network,
+                "volume" => Resource::Volume,
+                "secret" => Resource::Secret,
+                _ => Resource::Image,
+            };
+
+            match command.as_str() {
+                // The name of a secret is followed by its source
+                "create" if resource == Resource::Secret => Some((
+                    Action::Create,
+                    resource,
+                    rest.len().checked_sub(2).map(|i| rest[i].clone()),
+                )),
+                "create" => Some((Action::Create, resource, last)),
+                "rm" => Some((Action::Remove, resource, last)),
+                "prune" => Some((Action::Prune, resource, None)),
+                _ => None,
+            }
+        }
+        [kube, command, ..] if kube == "kube" => match command.as_str() {
+            "play" => Some((Action::Play, Resource::Kube, None)),
+            "down" => Some((Action::Remove, Resource::Kube, None)),
+            _ => None,
+        },
+        [command, rest @ ..] => {
+            let action = match command.as_str() {
+                "start" => Action::Start,
+                "stop" => Action::Stop,
+                "restart" => Action::Restart,
+                "kill" => Action::Kill,
+                "pause" => Action::Pause,
+                "unpause" => Action::Unpause,
+                "rm" => Action::Remove,
+                "exec" => return Some((Action::Run, Resource::Container, None)),
+                "cp" => return Some((Action::Copy, Resource::Container, container(rest))),
+                "rmi" => return Some((Action::Remove, Resource::Image, last)),
+                _ => return None,
+            };
+
+            Some((action, Resource::Container, last))
+        }
+        [] => None,
+    }
+}
+
+/// Replaces the data of the Kubernetes secrets in the input of `kube play` with shell variables, so
+/// that a plan can be shared, returning the variables along with what they hold
+fn redact(input: &str) -> (String, IndexMap<String, String>) {
+    let mut variables = IndexMap::new();
+    let documents = serde_yaml::Deserializer::from_str(input)
+        .map(|document| {
+            let mut document = Value::deserialize(document).ok()?;
+
+            if document.get("kind").and_then(Value::as_str) == Some("Secret") {
+                let name = document
+                    .get("metadata")
+                    .and_then(|metadata| metadata.get("name"))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+
+                for (field, encoding) in [("data", "base64-encoded "), ("stringData", "")] {
+                    if let Some(Value::Mapping(data)) = document.get_mut(field) {
+                        for (key, value) in data.iter_mut() {
+                            let key = key.as_str().unwrap_or_default();
+                            let variable =
+                                format!("SECRET_{}", AsShoutySnakeCase(format!("{name}_{key}")));
+
+                            *value = Value::from(format!("${{{variable}}}"));
+                            variables.insert(
+                                variable,
+                                format!("{encoding}value of key {key} of secret {name}"),
+                            );
+                        }
+                    }
+                }
+            }
+
+            serde_yaml::to_string(&document).ok()
+        })
+        .collect::<Option<Vec<_>>>();
+
+    match documents {
+        Some(documents) if !variables.is_empty() => (documents.join("---\n"), variables),
+        _ => (input.to_string(), IndexMap::new()),
+    }
+}
+
+/// Escapes the input of an unquoted heredoc, except for the expansion of `variables`
+fn escape<'a>(input: &str, variables: impl IntoIterator<Item = &'a String>) -> String {
+    variables.into_iter().fold(
+        input
+            .replace('\\', "\\\\")
+            .replace('$', "\\$")
+            .replace('`', "\\`"),
+        |input, variable| input.replace(&format!("\\${{{variable}}}"), &format!("${{{variable}}}")),
+    )
+}
+
+/// Heredoc delimiter that no line of `input` matches
+fn delimiter(input: &str) -> String {
+    (0..)
+        .map(|i| match i {
+            0 => String::from("EOF"),
+            i => format!("EOF_{i}"),
+        })
+        .find(|delimiter| !input.lines().any(|line| line == delimiter))
+        .unwrap()
+}
+
+/// Steps of a dry run, printed as they come in text format and at the end otherwise
+pub(crate) struct Plan {
+    format: PlanFormat,
+    steps: RefCell<Vec<Step>>,
+    reason: RefCell<Option<String>>,
+}
+
+impl Plan {
+    pub(crate) fn new(format: PlanFormat) -> Self {
+        Self {
+            format,
+            steps: RefCell::new(Vec::new()),
+            reason: RefCell::new(None),
+        }
+    }
+
+    /// Sets the reason of the steps recorded from now on, instead of the default one of their action,
+    /// returning the previous one
+    pub(crate) fn set_reason(&self, reason: Option<String>) -> Option<String> {
+        self.reason.replace(reason)
+    }
+
+    fn removal(&self, resource: Resource, name: &str) -> Option<String> {
+        self.steps
+            .borrow()
+            .iter()
+            .rev()
+            .find(|step| step.resource == resource && step.name.as_deref() == Some(name))
+            .filter(|step| step.action == Action::Remove)
+            .map(|step| step.reason.clone())
+    }
+
+    /// Whether the last step on a resource removes it
+    pub(crate) fn removed(&self, resource: Resource, name: &str) -> bool {
+        self.removal(resource, name).is_some()
+    }
+
+    /// Records an invocation that a dry run skips
+    pub(crate) fn command(&self, args: &[String], command: String, input: Option<&str>) {
+        if self.format == PlanFormat::Text {
+            println!("`{command}`");
+        }
+
+        let Some((action, resource, name)) = classify(args) else {
+            return;
+        };
+        let reason = self
+            .reason
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| match action {
+                Action::Create => name
+                    .as_ref()
+                    .and_then(|name| self.removal(resource, name))
+                    .unwrap_or_else(|| String::from("does not exist")),
+                _ => String::from("requested"),
+            });
+
+        let (input, variables) = input.map(redact).unzip();
+
+        self.steps.borrow_mut().push(Step {
+            action,
+            resource,
+            name,
+            reason,
+            command: Some(command),
+            input,
+            variables: variables.unwrap_or_default(),
+        });
+    }
+
+    /// Records a resource left as it is
+    pub(crate) fn keep(&self, resource: Resource, name: &str, reason: &str) {
+        self.steps.borrow_mut().push(Step {
+            action: Action::Keep,
+            resource,
+            name: Some(name.to_string()),
+            reason: reason.to_string(),
+            command: None,
+            input: None,
+            variables: IndexMap::new(),
+        });
+    }
+
+    /// Records a directory to create on the host, which only the script format shows
+    pub(crate) fn directory(&self, path: &str) {
+        self.steps.borrow_mut().push(Step {
+            action: Action::Create,
+            resource: Resource::Directory,
+            name: Some(path.to_string()),
+            reason: String::from("source of a bind mount"),
+            command: Some(format!("mkdir -p {}", shell_words::quote(path))),
+            input: None,
+            variables: IndexMap::new(),
+        });
+    }
+
+    /// Renders the recorded steps, if the format defers them to the end of the run
+    pub(crate) fn render(&self) -> Result<Option<String>> {
+        let steps = self.steps.borrow();
+
+        Ok(match self.format {
+            PlanFormat::Text => None,
+            PlanFormat::Script => {
+                let mut script = String::from("#!/bin/sh\nset -e\n");
+                let variables = steps
+                    .iter()
+                    .flat_map(|step| &step.variables)
+                    .collect::<IndexMap<_, _>>();
+
+                // The script stops before any mutation unless the data left out of it is given
+                if !variables.is_empty() {
+                    writeln!(script, "\n# Secret data left out of the plan")?;
+
+                    for (variable, description) in variables {
+                        writeln!(script, ": \"${{{variable}:?{description}}}\"")?;
+                    }
+                }
+
+                for step in steps.iter() {
+                    write!(script, "\n# {} {}", step.action, step.resource)?;
+
+                    if let Some(name) = &step.name {
+                        write!(script, " {name}")?;
+                    }
+
+                    writeln!(script, ": {}", step.reason)?;
+
+                    if let Some(command) = &step.command {
+                        match &step.input {
+                            Some(input) if !step.variables.is_empty() => {
+                                let input = escape(input, step.variables.keys());
+                                let delimiter = delimiter(&input);
+
+                                writeln!(
+                                    script,
+                                    "{command} <<{delimiter}\n{}{}{delimiter}",
+                                    input,
+                                    if input.ends_with('\n') { "" } else { "\n" }
+                                )?;
+                            }
+                            Some(input) => {
+                                let delimiter = delimiter(input);
+
+                                writeln!(
+                                    script,
+                                    "{command} <<'{delimiter}'\n{}{}{delimiter}",
+                                    input,
+                                    if input.ends_with('\n') { "" } else { "\n" }
+                                )?;
+                            }
+                            None => writeln!(script, "{command}")?,
+                        }
+                    }
+                }
+
+                Some(script)
+            }
+            PlanFormat::Json => Some(serde_json::to_string_pretty(&*steps)? + "\n"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn args(command: &str) -> Vec<String> {
+        shell_words::split(command).unwrap()
+    }
+
+    #[test]
+    fn classification() {
+        for (command, expected) in [
+            (
+                "--runtime crun create --name proj_web_1 --network-alias web nginx",
+                Some((Action::Create, Resource::Container, Some("proj_web_1"))),
+            ),
+            (
+                "secret create --label a=b --env proj_token TOKEN",
+                Some((Action::Create, Resource::Secret, Some("proj_token"))),
+            ),
+            (
+                "network rm proj_default",
+                Some((Action::Remove, Resource::Network, Some("proj_default"))),
+            ),
+            (
+                "stop --time 5 proj_web_1",
+                Some((Action::Stop, Resource::Container, Some("proj_web_1"))),
+            ),
+            (
+                "cp proj_web_1:/etc/hosts -",
+                Some((Action::Copy, Resource::Container, Some("proj_web_1"))),
+            ),
+            ("container exists proj_web_1", None),
+            ("wait --condition running proj_web_1", None),
+        ] {
+            assert_eq!(
+                classify(&args(command)),
+                expected.map(|(action, resource, name)| (action, resource, name.map(String::from))),
+                "{command}"
+            );
+        }
+    }
+
+    #[test]
+    fn redaction() {
+        let plan = Plan::new(PlanFormat::Script);
+
+        plan.command(
+            &args("kube play --replace -"),
+            String::from("podman kube play --replace -"),
+            Some("apiVersion: v1\nkind: Secret\nmetadata:\n  name: proj-token\ndata:\n  token: c2VjcmV0\n---\napiVersion: v1\nkind: Pod\nmetadata:\n  name: proj\nspec:\n  containers:\n  - args:\n    - echo $HOME\n"),
+        );
+
+        assert_eq!(
+            plan.render().unwrap().unwrap(),
+            r#"#!/bin/sh
+set -e
+
+# Secret data left out of the plan
+: "${SECRET_PROJ_TOKEN_TOKEN:?base64-encoded value of key token of secret proj-token}"
+
+# play kube: requested
+podman kube play --replace - <<EOF
+apiVersion: v1
+kind: Secret
+metadata:
+  name: proj-token
+data:
+  token: ${SECRET_PROJ_TOKEN_TOKEN}
+---
+apiVersion: v1
+kind: Pod
+metadata:
+  name: proj
+spec:
+  containers:
+  - args:
+    - echo \$HOME
+EOF
+"#
+        );
+        assert_eq!(delimiter("EOF\nEOF_1\n"), "EOF_2");
+    }
+
+    #[test]
+    fn script() {
+        let plan = Plan::new(PlanFormat::Script);
+
+        plan.keep(Resource::Network, "proj_default", "already exists");
+        plan.set_reason(Some(String::from("configuration changed")));
+        plan.command(
+            &args("rm --depend --ignore proj_web_1"),
+            String::from("podman rm --depend --ignore proj_web_1"),
+            None,
+        );
+        plan.set_reason(None);
+
+        assert!(plan.removed(Resource::Container, "proj_web_1"));
+        assert!(!plan.removed(Resource::Volume, "proj_data"));
+
+        plan.command(
+            &args("create --name proj_web_1 nginx"),
+            String::from("podman create --name proj_web_1 nginx"),
+            None,
+        );
+        plan.command(
+            &args("kube play --replace -"),
+            String::from("podman kube play --replace -"),
+            Some("kind: Pod"),
+        );
+
+        assert_eq!(
+            plan.render().unwrap().unwrap(),
+            "#!/bin/sh
+set -e
+
+# keep network proj_default: already exists
+
+# remove container proj_web_1: configuration changed
+podman rm --depend --ignore proj_web_1
+
+# create container proj_web_1: configuration changed
+podman create --name proj_web_1 nginx
+
+# play kube: requested
+podman kube play --replace - <<'EOF'
+kind: Pod
+EOF
+"
+        );
+    }
+}
@@ -0,0 +1,805 @@
+use std::env;
+
+use clap::crate_version;
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use serde_with::skip_serializing_none;
+
+use crate::{
+    compose::types::{
+        Compose, FileReference, FileReferenceType, Network, PodmanExtension, Service,
+        ServiceNetwork, ServiceVolume, ServiceVolumeType, Volume,
+    },
+    utils::Digest,
+};
+
+/// Payload of the REST API creating a resource, which an executor may send instead of its
+/// CLI arguments
+#[derive(Debug)]
+pub(crate) enum Payload {
+    Container(Value),
+    Pod(Value),
+    Network(Value),
+    Volume(Value),
+}
+
+/// Labels given as `key=value` arguments
+fn label_map<'a, I>(labels: I) -> Map<String, Value>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    labels
+        .into_iter()
+        .filter_map(|label| label.split_once('='))
+        .map(|(key, value)| (key.to_string(), Value::from(value)))
+        .collect()
+}
+
+/// Namespace of a `--network` or `--userns` mode, or `None` if it's a network name or has options
+fn namespace(mode: &str) -> Option<Value> {
+    match mode.split_once(':') {
+        Some(("ns", path)) => Some(json!({ "nsmode": "path", "value": path })),
+        Some(_) => None,
+        None => matches!(
+            mode,
+            "bridge"
+                | "host"
+                | "none"
+                | "private"
+                | "slirp4netns"
+                | "pasta"
+                | "auto"
+                | "keep-id"
+                | "nomap"
+        )
+        .then(|| json!({ "nsmode": mode })),
+    }
+}
+
+/// Renders the libpod `PodSpecGenerator` of `POST /libpod/pods/create`, or `None` if the
+/// extension sets modes that only the CLI arguments render
+pub(crate) fn pod_payload(
+    name: &str,
+    x_podman: &PodmanExtension,
+    labels: &[String],
+) -> Option<Value> {
+    let userns = match x_podman.userns.as_deref() {
+        Some(userns) => Some(namespace(userns)?),
+        None => None,
+    };
+    let mut netns = None;
+    let mut networks = Map::new();
+
+    for network in &x_podman.network {
+        match namespace(network) {
+            Some(mode) => netns = Some(mode),
+            None if network.contains(':') => return None,
+            None => {
+                networks.insert(network.clone(), json!({}));
+            }
+        }
+    }
+
+    if !networks.is_empty() {
+        netns.get_or_insert_with(|| json!({ "nsmode": "bridge" }));
+    }
+
+    Some(json!({
+        "name": name,
+        "labels": label_map(labels),
+        "shared_namespaces": if x_podman.share.is_empty() {
+            vec![String::from("none")]
+        } else {
+            x_podman.share.clone()
+        },
+        "userns": userns,
+        "no_infra": x_podman.infra == Some(false),
+        "infra_image": x_podman.infra_image,
+        "infra_command": x_podman.infra_command.as_ref().map(|command| [command]),
+        "netns": netns,
+        "Networks": networks,
+        "hostname": x_podman.hostname,
+        "hostadd": x_podman.add_host,
+    }))
+}
+
+/// Renders the libpod network of `POST /libpod/networks/create`, or `None` if it sets IP ranges,
+/// which only the CLI arguments render
+pub(crate) fn network_payload(network: &Network, labels: &[String]) -> Option<Value> {
+    let pools = network
+        .ipam
+        .iter()
+        .flat_map(|ipam| &ipam.config)
+        .collect::<Vec<_>>();
+
+    if pools.iter().any(|pool| pool.ip_range.is_some()) {
+        return None;
+    }
+
+    let mut label_map = label_map(labels);
+
+    label_map.extend(
+        network
+            .labels
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::from(value.clone()))),
+    );
+
+    Some(json!({
+        "name": network.name,
+        "driver": network.driver,
+        "options": network.driver_opts,
+        "ipv6_enabled": network.enable_ipv6.unwrap_or_default(),
+        "internal": network.internal.unwrap_or_default(),
+        "labels": label_map,
+        "ipam_options": network
+            .ipam
+            .as_ref()
+            .and_then(|ipam| ipam.driver.as_ref())
+            .map(|driver| json!({ "driver": driver })),
+        "subnets": pools
+            .iter()
+            .filter_map(|pool| {
+                pool.subnet
+                    .as_ref()
+                    .map(|subnet| json!({ "subnet": subnet, "gateway": pool.gateway }))
+            })
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Renders the options of `POST /libpod/volumes/create`
+pub(crate) fn volume_payload(volume: &Volume, labels: &[String]) -> Value {
+    let mut label_map = label_map(labels);
+
+    label_map.extend(
+        volume
+            .labels
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::from(value.clone()))),
+    );
+
+    json!({
+        "Name": volume.name,
+        "Driver": volume.driver,
+        "Labels": label_map,
+        "Options": volume.driver_opts,
+    })
+}
+
+/// Short digest of each field set in the service, to tell which ones changed since its creation
+pub(crate) fn field_hashes(service: &Service) -> IndexMap<String, String> {
+    match serde_yaml::to_value(service) {
+        Ok(serde_yaml::Value::Mapping(fields)) => fields
+            .iter()
+            .filter_map(|(key, value)| {
+                key.as_str()
+                    .map(|key| (key.to_string(), value.digest()[..8].to_string()))
+            })
+            .collect(),
+        _ => IndexMap::new(),
+    }
+}
+
+/// Service fields that [`ContainerSpec::to_payload`] renders, or that only matter to compose
+const PAYLOAD_FIELDS: [&str; 33] = [
+    "attach",
+    "cap_add",
+    "cap_drop",
+    "command",
+    "container_name",
+    "depends_on",
+    "dns",
+    "dns_opt",
+    "dns_search",
+    "entrypoint",
+    "environment",
+    "extra_hosts",
+    "hostname",
+    "image",
+    "init",
+    "labels",
+    "mac_address",
+    "networks",
+    "ports",
+    "privileged",
+    "profiles",
+    "pull_policy",
+    "read_only",
+    "restart",
+    "scale",
+    "secrets",
+    "stdin_open",
+    "stop_grace_period",
+    "sysctls",
+    "tty",
+    "user",
+    "volumes",
+    "working_dir",
+];
+
+/// Container of a service, with the project resources it uses resolved to their real names
+#[skip_serializing_none]
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct ContainerSpec {
+    pub(crate) name: String,
+    pub(crate) pod: Option<String>,
+    pub(crate) network_alias: String,
+    pub(crate) requires: Vec<String>,
+    pub(crate) labels: IndexMap<String, String>,
+    pub(crate) pull: Option<String>,
+    pub(crate) networks: IndexMap<String, Option<ServiceNetwork>>,
+    pub(crate) mac_address: Option<String>,
+    pub(crate) volumes: Vec<ServiceVolume>,
+    pub(crate) secrets: Vec<FileReference>,
+    pub(crate) interactive: bool,
+    pub(crate) detach: bool,
+    pub(crate) rm: bool,
+    pub(crate) tty: bool,
+    /// Remaining options of the service, rendered by `Service::to_args`
+    pub(crate) service: Service,
+}
+
+impl ContainerSpec {
+    pub(crate) fn new(file: &Compose, service_name: &str, service: &Service, name: String) -> Self {
+        let project_name = file.name.as_ref().unwrap();
+
+        Self {
+            name,
+            pod: file.has_pod().then(|| project_name.clone()),
+            network_alias: service_name.to_string(),
+            requires: Vec::new(),
+            labels: [("version", crate_version!()), ("project", project_name)]
+                .into_iter()
+                .map(|(key, value)| (format!("io.podman.compose.{key}"), value.to_string()))
+                .collect(),
+            pull: service.pull_policy.as_ref().map(ToString::to_string),
+            networks: service
+                .networks
+                .iter()
+                .map(|(name, network)| (file.networks[name].name.clone().unwrap(), network.clone()))
+                .collect(),
+            mac_address: service.mac_address.clone(),
+            volumes: service
+                .volumes
+                .iter()
+                .map(|volume| match &volume.r#type {
+                    ServiceVolumeType::Volume(Some(source)) => ServiceVolume {
+                        r#type: ServiceVolumeType::Volume(file.volumes[source].name.clone()),
+                        ..volume.clone()
+                    },
+                    _ => volume.clone(),
+                })
+                .collect(),
+            secrets: service
+                .secrets
+                .iter()
+                .map(|secret| FileReference {
+                    source: file.secrets[&secret.source].name.clone().unwrap(),
+                    ..secret.clone()
+                })
+                .collect(),
+            interactive: false,
+            detach: false,
+            rm: false,
+            tty: false,
+            service: service.clone(),
+        }
+    }
+
+    /// Adds a label in the `io.podman.compose.` namespace
+    pub(crate) fn label(&mut self, key: &str, value: impl Into<String>) {
+        self.labels
+            .insert(format!("io.podman.compose.{key}"), value.into());
+    }
+
+    /// Renders the arguments of `podman create` or `podman run`
+    pub(crate) fn to_args(&self, command: &str) -> Vec<String> {
+        let (global_args, service_args) = self.service.to_args();
+        let networks = self.networks.iter().map(|(name, network)| {
+            let network = network
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+
+            match &self.mac_address {
+                Some(mac_address) if network.is_empty() => format!("{name}:mac={mac_address}"),
+                Some(mac_address) => format!("{name}{network},mac={mac_address}"),
+                None => format!("{name}{network}"),
+            }
+        });
+
+        global_args
+            .into_iter()
+            .chain([command.to_string()])
+            .chain(if self.interactive {
+                vec![String::from("--interactive")]
+            } else {
+                vec![]
+            })
+            .chain(
+                self.pod
+                    .iter()
+                    .flat_map(|pod| [String::from("--pod"), pod.clone()]),
+            )
+            .chain([
+                String::from("--name"),
+                self.name.clone(),
+                String::from("--network-alias"),
+                self.network_alias.clone(),
+            ])
+            .chain(
+                self.requires
+                    .iter()
+                    .flat_map(|requirement| [String::from("--requires"), requirement.clone()]),
+            )
+            .chain(
+                self.labels
+                    .iter()
+                    .flat_map(|(key, value)| [String::from("--label"), format!("{key}={value}")]),
+            )
+            .chain(
+                self.pull
+                    .iter()
+                    .flat_map(|pull| [String::from("--pull"), pull.clone()]),
+            )
+            .chain(networks.flat_map(|network| [String::from("--network"), network]))
+            .chain(self.volumes.iter().flat_map(|volume| {
+                [
+                    String::from(match volume.r#type {
+                        ServiceVolumeType::Volume(_) | ServiceVolumeType::Bind(_) => "--volume",
+                        ServiceVolumeType::Tmpfs => "--tmpfs",
+                    }),
+                    volume.to_string(),
+                ]
+            }))
+            .chain(
+                self.secrets
+                    .iter()
+                    .flat_map(|secret| [String::from("--secret"), secret.to_string()]),
+            )
+            .chain(if self.detach {
+                vec![String::from("--detach")]
+            } else {
+                vec![]
+            })
+            .chain(if self.rm {
+                vec![String::from("--rm")]
+            } else {
+                vec![]
+            })
+            .chain(if self.tty {
+                vec![String::from("--tty")]
+            } else {
+                vec![]
+            })
+            .chain(service_args)
+            .collect()
+    }
+
+    /// Renders the libpod `SpecGenerator` of `POST /libpod/containers/create`, or `None` if the
+    /// service sets options that only the CLI arguments render
+    pub(crate) fn to_payload(&self) -> Option<Value> {
+        let service = &self.service;
+
+        if field_hashes(service)
+            .keys()
+            .any(|field| !PAYLOAD_FIELDS.contains(&field.as_str()))
+        {
+            return None;
+        }
+
+        let mut volumes = Vec::new();
+        let mut mounts = Vec::new();
+
+        for volume in &self.volumes {
+            let mut options = Vec::new();
+
+            match &volume.r#type {
+                ServiceVolumeType::Volume(Some(source)) => {
+                    if volume
+                        .volume
+                        .as_ref()
+                        .and_then(|volume| volume.nocopy)
+                        .unwrap_or_default()
+                    {
+                        options.push(String::from("nocopy"));
+                    }
+
+                    if volume.read_only.unwrap_or_default() {
+                        options.push(String::from("ro"));
+                    }
+
+                    volumes.push(json!({
+                        "Name": source,
+                        "Dest": volume.target,
+                        "Options": options,
+                    }));
+                }
+                // Anonymous volumes are only named by podman itself
+                ServiceVolumeType::Volume(None) => return None,
+                ServiceVolumeType::Bind(source) => {
+                    options.push(String::from("rbind"));
+                    options.extend(
+                        volume
+                            .bind
+                            .iter()
+                            .flat_map(|bind| bind.propagation.iter().chain(&bind.selinux).cloned()),
+                    );
+
+                    if volume.read_only.unwrap_or_default() {
+                        options.push(String::from("ro"));
+                    }
+
+                    mounts.push(json!({
+                        "destination": volume.target,
+                        "type": "bind",
+                        "source": source,
+                        "options": options,
+                    }));
+                }
+                ServiceVolumeType::Tmpfs => {
+                    if let Some(tmpfs) = &volume.tmpfs {
+                        if let Some(size) = tmpfs.size {
+                            options.push(format!("size={size}"));
+                        }
+
+                        if let Some(mode) = tmpfs.mode {
+                            options.push(format!("mode={mode}"));
+                        }
+                    }
+
+                    if volume.read_only.unwrap_or_default() {
+                        options.push(String::from("ro"));
+                    }
+
+                    mounts.push(json!({
+                        "destination": volume.target,
+                        "type": "tmpfs",
+                        "source": "tmpfs",
+                        "options": options,
+                    }));
+                }
+            }
+        }
+
+        let ports = service
+            .ports
+            .iter()
+            .map(|port| {
+                Some(json!({
+                    "container_port": port.target.parse::<u16>().ok()?,
+                    "host_port": port
+                        .published
+                        .as_deref()
+                        .map(str::parse::<u16>)
+                        .transpose()
+                        .ok()?
+                        .unwrap_or_default(),
+                    "host_ip": port.host_ip,
+                    "protocol": port.protocol,
+                }))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let secrets = self
+            .secrets
+            .iter()
+            .filter(|secret| !matches!(secret.r#type, Some(FileReferenceType::Env)))
+            .map(|secret| {
+                Some(json!({
+                    "Source": secret.source,
+                    "Target": secret.target.clone().unwrap_or_default(),
+                    "UID": secret.uid.as_deref().map(str::parse::<u32>).transpose().ok()?,
+                    "GID": secret.gid.as_deref().map(str::parse::<u32>).transpose().ok()?,
+                    "Mode": secret.mode.unwrap_or(0o444),
+                }))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let secret_env = self
+            .secrets
+            .iter()
+            .filter(|secret| matches!(secret.r#type, Some(FileReferenceType::Env)))
+            .map(|secret| {
+                (
+                    secret
+                        .target
+                        .clone()
+                        .unwrap_or_else(|| secret.source.clone()),
+                    Value::from(secret.source.clone()),
+                )
+            })
+            .collect::<Map<_, _>>();
+        let networks = self
+            .networks
+            .iter()
+            .map(|(name, network)| {
+                let aliases = [&self.network_alias]
+                    .into_iter()
+                    .chain(network.iter().flat_map(|network| &network.aliases))
+                    .collect::<Vec<_>>();
+
+                (
+                    name.clone(),
+                    json!({
+                        "aliases": aliases,
+                        "static_ips": network
+                            .iter()
+                            .flat_map(|network| {
+                                network.ipv4_address.iter().chain(&network.ipv6_address)
+                            })
+                            .collect::<Vec<_>>(),
+                        "static_mac": self.mac_address,
+                    }),
+                )
+            })
+            .collect::<Map<_, _>>();
+
+        Some(json!({
+            "name": self.name,
+            "pod": self.pod,
+            "image": service.image,
+            "entrypoint": service.entrypoint,
+            "command": service.command,
+            "env": service
+                .environment
+                .iter()
+                .filter_map(|(key, value)| {
+                    value
+                        .clone()
+                        .or_else(|| env::var(key).ok())
+                        .map(|value| (key.clone(), Value::from(value)))
+                })
+                .collect::<Map<_, _>>(),
+            "labels": service.labels.iter().chain(&self.labels).collect::<IndexMap<_, _>>(),
+            "dependencyContainers": self.requires,
+            "netns": (!networks.is_empty()).then(|| json!({ "nsmode": "bridge" })),
+            "Networks": networks,
+            "portmappings": ports,
+            "volumes": volumes,
+            "mounts": mounts,
+            "secrets": secrets,
+            "secret_env": secret_env,
+            "hostname": service.hostname,
+            "hostadd": service
+                .extra_hosts
+                .iter()
+                .map(|(host, ip)| format!("{host}:{ip}"))
+                .collect::<Vec<_>>(),
+            "dns_server": service.dns,
+            "dns_option": service.dns_opt,
+            "dns_search": service.dns_search,
+            "cap_add": service.cap_add,
+            "cap_drop": service.cap_drop,
+            "privileged": service.privileged.unwrap_or_default(),
+            "read_only_filesystem": service.read_only.unwrap_or_default(),
+            "init": service.init.unwrap_or_default(),
+            "user": service.user,
+            "work_dir": service.working_dir,
+            "sysctl": service.sysctls,
+            "restart_policy": service.restart.as_ref().map(ToString::to_string),
+            "stop_timeout": service
+                .stop_grace_period
+                .map(|stop_grace_period| stop_grace_period.as_secs()),
+            "stdin": self.interactive || service.stdin_open.unwrap_or_default(),
+            "terminal": self.tty || service.tty.unwrap_or_default(),
+            "remove": self.rm,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn file() -> Compose {
+        serde_yaml::from_str(
+            r#"
+            name: proj
+            services:
+              web:
+                image: nginx
+                mac_address: 92:d0:c6:0a:29:33
+                networks:
+                  front:
+                    aliases: [www]
+                volumes:
+                  - data:/data
+                  - type: tmpfs
+                    target: /tmp
+                secrets:
+                  - token
+            networks:
+              front:
+                name: proj_front
+            volumes:
+              data:
+                name: proj_data
+            secrets:
+              token:
+                name: proj_token
+                environment: TOKEN
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn resolve() {
+        let file = file();
+        let spec = ContainerSpec::new(
+            &file,
+            "web",
+            &file.services["web"],
+            String::from("proj_web_1"),
+        );
+
+        assert_eq!(spec.pod.as_deref(), Some("proj"));
+        assert_eq!(spec.networks.keys().collect::<Vec<_>>(), ["proj_front"]);
+        assert!(matches!(
+            &spec.volumes[0].r#type,
+            ServiceVolumeType::Volume(Some(name)) if name == "proj_data"
+        ));
+        assert!(matches!(spec.volumes[1].r#type, ServiceVolumeType::Tmpfs));
+        assert_eq!(spec.secrets[0].source, "proj_token");
+        assert_eq!(spec.labels["io.podman.compose.project"], "proj");
+        assert!(!spec.labels.contains_key("io.podman.compose.config-fields"));
+    }
+
+    #[test]
+    fn render() {
+        let file = file();
+        let mut spec = ContainerSpec::new(
+            &file,
+            "web",
+            &file.services["web"],
+            String::from("proj_web_run_1"),
+        );
+
+        spec.label("oneoff", "true");
+        spec.interactive = true;
+        spec.rm = true;
+
+        let args = spec.to_args("run");
+
+        assert_eq!(&args[..3], ["run", "--interactive", "--pod"]);
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--network", "proj_front:alias=www,mac=92:d0:c6:0a:29:33"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--label", "io.podman.compose.oneoff=true"]));
+        assert!(args.contains(&String::from("--rm")));
+        assert_eq!(args.last().map(String::as_str), Some("nginx"));
+    }
+
+    #[test]
+    fn payload() {
+        let mut file = file();
+        let mut spec = ContainerSpec::new(
+            &file,
+            "web",
+            &file.services["web"],
+            String::from("proj_web_1"),
+        );
+
+        spec.requires = vec![String::from("proj_db_1")];
+        spec.label("service", "web");
+
+        let payload = spec.to_payload().unwrap();
+
+        assert_eq!(payload["name"], "proj_web_1");
+        assert_eq!(payload["pod"], "proj");
+        assert_eq!(payload["image"], "nginx");
+        assert_eq!(payload["labels"]["io.podman.compose.service"], "web");
+        assert_eq!(payload["dependencyContainers"], json!(["proj_db_1"]));
+        assert_eq!(
+            payload["Networks"],
+            json!({
+                "proj_front": {
+                    "aliases": ["web", "www"],
+                    "static_ips": [],
+                    "static_mac": "92:d0:c6:0a:29:33",
+                },
+            })
+        );
+        assert_eq!(
+            payload["volumes"],
+            json!([{ "Name": "proj_data", "Dest": "/data", "Options": [] }])
+        );
+        assert_eq!(payload["mounts"][0]["type"], "tmpfs");
+        assert_eq!(payload["mounts"][0]["destination"], "/tmp");
+        assert_eq!(payload["secrets"][0]["Source"], "proj_token");
+        assert_eq!(payload["secrets"][0]["Mode"], 0o444);
+
+        file.services["web"].cpus = Some(0.5);
+
+        let spec = ContainerSpec::new(
+            &file,
+            "web",
+            &file.services["web"],
+            String::from("proj_web_1"),
+        );
+
+        assert_eq!(spec.to_payload(), None);
+    }
+
+    #[test]
+    fn payloads() {
+        let file = serde_yaml::from_str::<Compose>(
+            r#"
+            name: proj
+            services: {}
+            networks:
+              front:
+                name: proj_front
+                driver: bridge
+                ipam:
+                  config:
+                    - subnet: 10.0.0.0/24
+                      gateway: 10.0.0.1
+              back:
+                name: proj_back
+                ipam:
+                  config:
+                    - subnet: 10.0.1.0/24
+                      ip_range: 10.0.1.0/28
+            volumes:
+              data:
+                name: proj_data
+                labels:
+                  backup: daily
+            x-podman:
+              share: [net]
+              userns: keep-id
+              network: [proj_front]
+            "#,
+        )
+        .unwrap();
+        let labels = [String::from("io.podman.compose.project=proj")];
+
+        assert_eq!(
+            pod_payload("proj", file.x_podman.as_ref().unwrap(), &labels),
+            Some(json!({
+                "name": "proj",
+                "labels": { "io.podman.compose.project": "proj" },
+                "shared_namespaces": ["net"],
+                "userns": { "nsmode": "keep-id" },
+                "no_infra": false,
+                "infra_image": null,
+                "infra_command": null,
+                "netns": { "nsmode": "bridge" },
+                "Networks": { "proj_front": {} },
+                "hostname": null,
+                "hostadd": [],
+            }))
+        );
+        assert_eq!(
+            pod_payload(
+                "proj",
+                &PodmanExtension {
+                    userns: Some(String::from("keep-id:uid=1000")),
+                    ..PodmanExtension::default()
+                },
+                &labels
+            ),
+            None
+        );
+        assert_eq!(
+            network_payload(&file.networks["front"], &labels).unwrap()["subnets"],
+            json!([{ "subnet": "10.0.0.0/24", "gateway": "10.0.0.1" }])
+        );
+        assert_eq!(network_payload(&file.networks["back"], &labels), None);
+        assert_eq!(
+            volume_payload(&file.volumes["data"], &labels),
+            json!({
+                "Name": "proj_data",
+                "Driver": null,
+                "Labels": { "io.podman.compose.project": "proj", "backup": "daily" },
+                "Options": {},
+            })
+        );
+    }
+}
@@ -1,6 +1,11 @@
-use std::collections::VecDeque;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display, Formatter},
+};
 
-use serde::Deserialize;
+use clap::ValueEnum;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, with_prefix, DisplayFromStr};
 
 with_prefix!(prefix_io_podman_compose "io.podman.compose.");
@@ -9,39 +14,179 @@ with_prefix!(prefix_io_podman_compose "io.podman.compose.");
 #[serde(rename_all = "PascalCase")]
 pub(crate) struct Container {
     pub(crate) id: String,
+    #[serde(default)]
+    pub(crate) image: String,
+    pub(crate) command: Option<Vec<String>>,
+    #[serde(default)]
+    pub(crate) created: i64,
+    #[serde(default)]
+    pub(crate) started_at: i64,
+    #[serde(default)]
+    pub(crate) exited_at: i64,
+    #[serde(default)]
+    pub(crate) exit_code: i32,
     #[serde(with = "prefix_io_podman_compose")]
     pub(crate) labels: Option<ContainerLabels>,
     pub(crate) names: VecDeque<String>,
+    pub(crate) ports: Option<Vec<Port>>,
+    pub(crate) state: Option<String>,
+    /// Status of the container, which is its health if it has a healthcheck
+    pub(crate) status: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Port {
+    #[serde(default)]
+    pub(crate) host_ip: String,
+    pub(crate) container_port: u16,
+    pub(crate) host_port: u16,
+    #[serde(default = "default_range")]
+    pub(crate) range: u16,
+    pub(crate) protocol: String,
+}
+
+fn default_range() -> u16 {
+    1
 }
 
 #[serde_as]
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub(crate) struct ContainerLabels {
+    pub(crate) project: Option<String>,
+    #[serde(rename = "project.working-dir")]
+    pub(crate) working_dir: Option<String>,
+    #[serde(rename = "project.config-files")]
+    pub(crate) config_files: Option<String>,
+    #[serde(rename = "project.environment-file")]
+    pub(crate) environment_file: Option<String>,
     pub(crate) service: Option<String>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub(crate) container_number: Option<usize>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub(crate) oneoff: Option<bool>,
+    /// Short digest of each field of the service, as `field:hash` pairs separated by commas
+    pub(crate) config_fields: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Event {
+    #[serde(rename = "ID")]
+    pub(crate) id: Option<String>,
+    pub(crate) name: String,
+    pub(crate) status: String,
+    pub(crate) r#type: String,
+    pub(crate) image: Option<String>,
+    #[serde(alias = "time")]
+    pub(crate) time: Option<Timestamp>,
+    #[serde(rename = "timeNano")]
+    pub(crate) time_nano: Option<i64>,
+    pub(crate) container_exit_code: Option<i32>,
+    #[serde(default)]
+    pub(crate) attributes: IndexMap<String, String>,
+}
+
+impl Event {
+    pub(crate) fn labels(&self) -> ContainerLabels {
+        let label = |name| self.attributes.get(&format!("io.podman.compose.{name}"));
+
+        ContainerLabels {
+            project: label("project").cloned(),
+            working_dir: label("project.working-dir").cloned(),
+            config_files: label("project.config-files").cloned(),
+            environment_file: label("project.environment-file").cloned(),
+            service: label("service").cloned(),
+            container_number: label("container-number").and_then(|n| n.parse().ok()),
+            oneoff: label("oneoff").and_then(|oneoff| oneoff.parse().ok()),
+            config_fields: label("config-fields").cloned(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum Timestamp {
+    Unix(i64),
+    Rfc3339(String),
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Timestamp::Unix(time) => write!(f, "{time}"),
+            Timestamp::Rfc3339(time) => write!(f, "{time}"),
+        }
+    }
+}
+
+#[derive(ValueEnum, Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Image {
+    pub(crate) id: String,
+    pub(crate) labels: Option<IndexMap<String, String>>,
 }
 
 #[derive(Deserialize, Debug)]
 pub(crate) struct Network {
     pub(crate) name: String,
+    pub(crate) labels: Option<IndexMap<String, String>>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub(crate) struct Pod {
+    #[serde(default)]
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) infra_id: String,
+    #[serde(default)]
+    pub(crate) containers: Vec<PodContainer>,
     #[serde(with = "prefix_io_podman_compose")]
     pub(crate) labels: Option<PodLabels>,
 }
 
-#[serde_as]
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct PodContainer {
+    pub(crate) id: String,
+    pub(crate) status: String,
+}
+
+#[serde_as]
+#[derive(Deserialize, Default, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub(crate) struct PodLabels {
+    pub(crate) project: Option<String>,
+    #[serde(rename = "project.working-dir")]
+    pub(crate) working_dir: Option<String>,
+    #[serde(rename = "project.config-files")]
+    pub(crate) config_files: Option<String>,
+    #[serde(rename = "project.environment-file")]
+    pub(crate) environment_file: Option<String>,
     pub(crate) config_hash: Option<String>,
 }
 
+impl From<ContainerLabels> for PodLabels {
+    /// Labels of the project that a container carries, for projects without a pod
+    fn from(labels: ContainerLabels) -> Self {
+        Self {
+            project: labels.project,
+            working_dir: labels.working_dir,
+            config_files: labels.config_files,
+            environment_file: labels.environment_file,
+            config_hash: None,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub(crate) struct Version {
@@ -58,4 +203,19 @@ pub(crate) struct VersionClient {
 #[serde(rename_all = "PascalCase")]
 pub(crate) struct Volume {
     pub(crate) name: String,
+    pub(crate) labels: Option<IndexMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Secret {
+    pub(crate) spec: SecretSpec,
+    pub(crate) secret_data: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct SecretSpec {
+    pub(crate) name: String,
+    pub(crate) labels: Option<IndexMap<String, String>>,
 }
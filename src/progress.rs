@@ -1,11 +1,15 @@
 use std::{borrow::Cow, cell::RefCell, fmt::Write, time::Duration};
 
 use anyhow::Result;
+use atty::Stream;
+use clap::ValueEnum;
 use console::style;
 use indicatif::{
     MultiProgress, ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressState, ProgressStyle,
 };
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::config::Config;
 
@@ -36,8 +40,31 @@ static SPINNER_ERROR_STYLE: Lazy<ProgressStyle> = Lazy::new(|| {
         .unwrap()
 });
 
+#[derive(ValueEnum, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProgressMode {
+    #[default]
+    Auto,
+    Tty,
+    Plain,
+    Json,
+    Quiet,
+}
+
+impl ProgressMode {
+    fn resolve(self, config: &Config) -> Self {
+        match self {
+            _ if config.dry_run => ProgressMode::Quiet,
+            ProgressMode::Auto if atty::is(Stream::Stderr) => ProgressMode::Tty,
+            ProgressMode::Auto => ProgressMode::Plain,
+            mode => mode,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Progress {
+    mode: ProgressMode,
     progress: MultiProgress,
     header: ProgressBar,
     spinners: RefCell<Vec<Spinner>>,
@@ -45,10 +72,11 @@ pub(crate) struct Progress {
 
 impl Progress {
     pub(crate) fn new(config: &Config) -> Self {
-        let progress = MultiProgress::with_draw_target(if config.dry_run {
-            ProgressDrawTarget::hidden()
-        } else {
+        let mode = config.progress.resolve(config);
+        let progress = MultiProgress::with_draw_target(if mode == ProgressMode::Tty {
             ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
         });
         let header = progress.add(
             ProgressBar::new(0)
@@ -57,6 +85,7 @@ impl Progress {
         );
 
         Self {
+            mode,
             progress,
             header,
             spinners: RefCell::new(Vec::new()),
@@ -79,10 +108,14 @@ impl Progress {
         );
         inner.enable_steady_tick(Duration::from_millis(100));
 
-        self.spinners.borrow_mut().push(Spinner {
+        let spinner = Spinner {
+            mode: self.mode,
             inner: inner.clone(),
             header: self.header.clone(),
-        });
+        };
+        spinner.emit(&spinner.inner.message(), None);
+
+        self.spinners.borrow_mut().push(spinner);
 
         let width = self
             .spinners
@@ -99,6 +132,7 @@ impl Progress {
         }
 
         Spinner {
+            mode: self.mode,
             inner,
             header: self.header.clone(),
         }
@@ -112,17 +146,54 @@ impl Progress {
 
 #[derive(Debug)]
 pub(crate) struct Spinner {
+    mode: ProgressMode,
     inner: ProgressBar,
     header: ProgressBar,
 }
 
 impl Spinner {
+    fn emit(&self, status: &str, error: Option<String>) {
+        let prefix = self.inner.prefix();
+        let prefix = prefix.trim();
+        let elapsed = self.inner.elapsed().as_secs_f64();
+
+        match self.mode {
+            ProgressMode::Plain => match error {
+                Some(error) => eprintln!("{prefix}  {status} {elapsed:.1}s: {}", error.trim()),
+                None if self.inner.is_finished() => eprintln!("{prefix}  {status} {elapsed:.1}s"),
+                None => eprintln!("{prefix}  {status}"),
+            },
+            ProgressMode::Json => {
+                let (kind, name) = prefix.split_once(' ').unwrap_or((prefix, ""));
+
+                eprintln!(
+                    "{}",
+                    json!({
+                        "kind": kind,
+                        "name": name,
+                        "status": status,
+                        "elapsed": elapsed,
+                        "error": error.as_deref().map(str::trim),
+                    })
+                );
+            }
+            ProgressMode::Auto | ProgressMode::Tty | ProgressMode::Quiet => {}
+        }
+    }
+
     pub(crate) fn finish_with_message(&self, message: impl Into<Cow<'static, str>>) {
         self.inner.set_style(SPINNER_FINISHED_STYLE.clone());
         self.inner.finish_with_message(message);
+        self.emit(&self.inner.message(), None);
 
         self.header.inc(1);
     }
+
+    fn finish_with_error(&self, error: String) {
+        self.inner.set_style(SPINNER_ERROR_STYLE.clone());
+        self.inner.finish_with_message("Error");
+        self.emit("Error", Some(error));
+    }
 }
 
 pub(crate) trait Finish {
@@ -131,11 +202,9 @@ pub(crate) trait Finish {
 
 impl<T> Finish for Result<T> {
     fn finish_with_message(self, spinner: Spinner, message: impl Into<Cow<'static, str>>) -> Self {
-        if self.is_ok() {
-            spinner.finish_with_message(message);
-        } else {
-            spinner.inner.set_style(SPINNER_ERROR_STYLE.clone());
-            spinner.inner.finish_with_message("Error");
+        match &self {
+            Ok(_) => spinner.finish_with_message(message),
+            Err(err) => spinner.finish_with_error(format!("{err:#}")),
         }
 
         self
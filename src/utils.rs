@@ -1,6 +1,11 @@
-use std::{env, error::Error, str::FromStr};
+use std::{
+    env,
+    error::Error,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use console::style;
 use serde::Serialize;
 use serde_with::formats::Separator;
@@ -52,6 +57,72 @@ where
     }
 }
 
+pub(crate) fn parse_timestamp(s: &str) -> Result<SystemTime> {
+    let (datetime, offset) = if let Some(datetime) = s.strip_suffix('Z') {
+        (datetime, 0)
+    } else if let Some((datetime, offset)) = s
+        .char_indices()
+        .rev()
+        .find(|(_, c)| *c == '+' || *c == '-')
+        .filter(|(i, _)| s[..*i].contains('T'))
+        .map(|(i, _)| s.split_at(i))
+    {
+        let (hours, minutes) = offset[1..]
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid timestamp: {s}"))?;
+        let seconds = hours.parse::<i64>()? * 3600 + minutes.parse::<i64>()? * 60;
+
+        (
+            datetime,
+            if offset.starts_with('-') {
+                -seconds
+            } else {
+                seconds
+            },
+        )
+    } else {
+        bail!("Invalid timestamp: {s}");
+    };
+    let time = humantime::parse_rfc3339_weak(datetime)?;
+
+    Ok(if offset < 0 {
+        time + Duration::from_secs(offset.unsigned_abs())
+    } else {
+        time - Duration::from_secs(offset.unsigned_abs())
+    })
+}
+
+/// Formats a duration the way Docker does, e.g. "About a minute" or "3 days"
+pub(crate) fn human_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let minutes = seconds / 60;
+    let hours = minutes / 60;
+
+    if seconds < 1 {
+        String::from("Less than a second")
+    } else if seconds == 1 {
+        String::from("1 second")
+    } else if seconds < 60 {
+        format!("{seconds} seconds")
+    } else if minutes == 1 {
+        String::from("About a minute")
+    } else if minutes < 60 {
+        format!("{minutes} minutes")
+    } else if hours == 1 {
+        String::from("About an hour")
+    } else if hours < 48 {
+        format!("{hours} hours")
+    } else if hours < 24 * 7 * 2 {
+        format!("{} days", hours / 24)
+    } else if hours < 24 * 30 * 2 {
+        format!("{} weeks", hours / 24 / 7)
+    } else if hours < 24 * 365 * 2 {
+        format!("{} months", hours / 24 / 30)
+    } else {
+        format!("{} years", hours / 24 / 365)
+    }
+}
+
 pub(crate) trait Digest {
     fn digest(&self) -> String;
 }
@@ -87,3 +158,50 @@ impl Separator for PathSeparator {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn timestamp_utc() {
+        assert_eq!(
+            parse_timestamp("2023-04-01T00:00:00.5Z").ok(),
+            Some(UNIX_EPOCH + Duration::from_millis(1_680_307_200_500))
+        );
+    }
+
+    #[test]
+    fn timestamp_offset() {
+        assert_eq!(
+            parse_timestamp("2023-04-01T08:00:00.000000001+08:00").ok(),
+            Some(UNIX_EPOCH + Duration::from_nanos(1_680_307_200_000_000_001))
+        );
+        assert_eq!(
+            parse_timestamp("2023-03-31T19:30:00-04:30").ok(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_680_307_200))
+        );
+    }
+
+    #[test]
+    fn timestamp_invalid() {
+        assert!(parse_timestamp("hello").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(
+            human_duration(Duration::from_millis(500)),
+            "Less than a second"
+        );
+        assert_eq!(human_duration(Duration::from_secs(59)), "59 seconds");
+        assert_eq!(human_duration(Duration::from_secs(90)), "About a minute");
+        assert_eq!(human_duration(Duration::from_secs(3 * 3600)), "3 hours");
+        assert_eq!(human_duration(Duration::from_secs(3 * 86400)), "3 days");
+        assert_eq!(human_duration(Duration::from_secs(800 * 86400)), "2 years");
+    }
+}